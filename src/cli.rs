@@ -0,0 +1,163 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use derive_more::Display;
+
+use crate::{hamfest_table, surplus_sale};
+
+const USAGE: &str = "\
+Usage: tdars-toolkit <command> <file> [options]
+
+Commands:
+    export <file> <output.xlsx>   Export the ledger to an Excel workbook
+    summary <file>                Print a summary of the file
+    validate <file>               Check the file for problems
+    audit <file> [--json]         Print the audit log
+
+Files are recognised by their extension, .tdars_auction for surplus sales
+and .tdars_club_table for club tables. Encrypted files are opened with the
+passphrase given with --passphrase, or in the TDARS_PASSPHRASE environment
+variable.
+
+Run without a command to start the toolkit as normal.";
+
+/// The first arguments which start the toolkit from the command line.
+/// Anything else, such as a file opened by the desktop, starts it as
+/// normal.
+const COMMANDS: [&str; 7] = [
+    "export", "summary", "validate", "audit", "help", "-h", "--help",
+];
+
+/// The environment variable a passphrase can be given in, to keep it out
+/// of the shell history
+const PASSPHRASE_VAR: &str = "TDARS_PASSPHRASE";
+
+/// Something to do with a datafile from the command line
+pub enum Command {
+    /// Export the ledger to an Excel workbook at the path
+    Export { output: PathBuf },
+    /// Print a summary of the file
+    Summary,
+    /// Check the file for problems
+    Validate,
+    /// Print the audit log, as JSON or as text
+    Audit { json: bool },
+}
+
+/// The reasons a command can fail
+#[derive(Display)]
+pub enum CliError {
+    #[display("{_0}\n\n{USAGE}")]
+    Usage(String),
+    #[display("{} is not a surplus sale or club table file", _0.display())]
+    UnknownFileType(PathBuf),
+    #[display("failed to open {}: {reason}", path.display())]
+    Open { path: PathBuf, reason: String },
+    #[display("failed to export: {_0}")]
+    Export(String),
+    #[display("failed to write {}: {reason}", path.display())]
+    Write { path: PathBuf, reason: String },
+    #[display("club table files have no audit log")]
+    NoAuditLog,
+    #[display("{_0} problem(s) found")]
+    ProblemsFound(usize),
+}
+
+/// Whether the toolkit was started with a command to run from the
+/// command line, rather than to start as normal
+#[must_use]
+pub fn requested() -> bool {
+    env::args()
+        .nth(1)
+        .is_some_and(|arg| COMMANDS.contains(&arg.as_str()))
+}
+
+/// Run the command given on the command line, returning the exit code.
+#[must_use]
+pub fn run() -> i32 {
+    let result = parse(env::args().skip(1)).and_then(|parsed| {
+        let Some((command, path, passphrase)) = parsed else {
+            println!("{USAGE}");
+            return Ok(());
+        };
+        execute(&command, &path, passphrase.as_deref())
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {e}");
+            1
+        }
+    }
+}
+
+/// Parse the command line arguments, after the program name, into the
+/// command, the file and the passphrase. Returns `None` if help was asked
+/// for.
+fn parse(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Option<(Command, PathBuf, Option<String>)>, CliError> {
+    let mut positional = vec![];
+    let mut json = false;
+    let mut passphrase = env::var(PASSPHRASE_VAR).ok();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" | "help" => return Ok(None),
+            "--json" => json = true,
+            "--passphrase" => {
+                passphrase = Some(args.next().ok_or_else(|| {
+                    CliError::Usage("--passphrase needs a passphrase".to_string())
+                })?);
+            }
+            flag if flag.starts_with("--") => {
+                return Err(CliError::Usage(format!("unknown option {flag}")));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let name = positional.next().unwrap_or_default();
+    let path = positional
+        .next()
+        .map(PathBuf::from)
+        .ok_or_else(|| CliError::Usage("no file given".to_string()))?;
+    let command = match name.as_str() {
+        "export" => Command::Export {
+            output: positional
+                .next()
+                .map(PathBuf::from)
+                .ok_or_else(|| CliError::Usage("no output file given".to_string()))?,
+        },
+        "summary" => Command::Summary,
+        "validate" => Command::Validate,
+        "audit" => Command::Audit { json },
+        _ => return Err(CliError::Usage(format!("unknown command {name}"))),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(CliError::Usage(format!("unexpected argument {extra}")));
+    }
+    Ok(Some((command, path, passphrase)))
+}
+
+/// Run a command on the file, choosing the tool it belongs to by its
+/// extension.
+fn execute(command: &Command, path: &Path, passphrase: Option<&str>) -> Result<(), CliError> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("tdars_auction") => surplus_sale::cli::execute(command, path, passphrase),
+        Some("tdars_club_table") => hamfest_table::cli::execute(command, path, passphrase),
+        _ => Err(CliError::UnknownFileType(path.to_path_buf())),
+    }
+}
+
+/// Write an export to the output path.
+pub fn write_export(output: &Path, data: &[u8]) -> Result<(), CliError> {
+    std::fs::write(output, data).map_err(|e| CliError::Write {
+        path: output.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    println!("Exported to {}", output.display());
+    Ok(())
+}
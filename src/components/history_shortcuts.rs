@@ -0,0 +1,46 @@
+use dioxus::prelude::*;
+use serde::Deserialize;
+
+/// Listens for undo and redo shortcuts anywhere in the window, except in
+/// text fields, which keep their own undo for the text typed in them.
+/// Replaces any listener added before, so that only the page showing
+/// receives them.
+const LISTENER: &str = r#"
+window.historyShortcuts?.abort();
+window.historyShortcuts = new AbortController();
+window.addEventListener("keydown", (e) => {
+    if (!(e.ctrlKey || e.metaKey) || e.target.closest("input, textarea")) {
+        return;
+    }
+    const key = e.key.toLowerCase();
+    if (key === "z" && !e.shiftKey) {
+        e.preventDefault();
+        dioxus.send("Undo");
+    } else if (key === "y" || (key === "z" && e.shiftKey)) {
+        e.preventDefault();
+        dioxus.send("Redo");
+    }
+}, { signal: window.historyShortcuts.signal });
+"#;
+
+/// A keyboard shortcut for moving through the undo history
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub enum HistoryShortcut {
+    /// Ctrl+Z
+    Undo,
+    /// Ctrl+Y or Ctrl+Shift+Z
+    Redo,
+}
+
+/// Call `on_shortcut` whenever an undo or redo shortcut is pressed while
+/// this component is shown.
+pub fn use_history_shortcuts(mut on_shortcut: impl FnMut(HistoryShortcut) + 'static) {
+    use_hook(|| {
+        spawn(async move {
+            let mut listener = document::eval(LISTENER);
+            while let Ok(shortcut) = listener.recv::<HistoryShortcut>().await {
+                on_shortcut(shortcut);
+            }
+        });
+    });
+}
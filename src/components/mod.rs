@@ -1,2 +1,8 @@
 mod callsign_entry;
 pub use callsign_entry::CallsignEntry;
+
+mod passphrase_prompt;
+pub use passphrase_prompt::{use_passphrase_prompt, PassphraseDialog, PassphrasePrompt};
+
+mod history_shortcuts;
+pub use history_shortcuts::{use_history_shortcuts, HistoryShortcut};
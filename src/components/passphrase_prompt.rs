@@ -0,0 +1,145 @@
+use dioxus::prelude::*;
+use dioxus_primitives::{
+    dialog::{DialogContent, DialogDescription, DialogRoot, DialogTitle},
+    label::Label,
+};
+use tokio::sync::oneshot;
+
+/// A request for a passphrase, waiting for the user to answer it
+struct Request {
+    /// Whether a new passphrase is being chosen, rather than entered
+    choosing: bool,
+    /// Sends the passphrase back to whoever asked for it
+    reply: oneshot::Sender<String>,
+}
+
+/// [`PassphrasePrompt`] asks the user for a passphrase from within an
+/// async event handler, through a [`PassphraseDialog`].
+#[derive(Clone, Copy, PartialEq)]
+pub struct PassphrasePrompt {
+    request: Signal<Option<Request>>,
+}
+
+impl PassphrasePrompt {
+    /// Ask for the passphrase of an encrypted file. Returns `None` if the
+    /// user cancels.
+    pub async fn enter(self) -> Option<String> {
+        self.ask(false).await
+    }
+
+    /// Ask for a new passphrase to encrypt a file with. Returns `None` if
+    /// the user cancels, or an empty passphrase if the file should not be
+    /// encrypted.
+    pub async fn choose(self) -> Option<String> {
+        self.ask(true).await
+    }
+
+    async fn ask(mut self, choosing: bool) -> Option<String> {
+        let (reply, answer) = oneshot::channel();
+        self.request.set(Some(Request { choosing, reply }));
+        answer.await.ok()
+    }
+}
+
+/// Create a [`PassphrasePrompt`], which must be shown with a
+/// [`PassphraseDialog`].
+pub fn use_passphrase_prompt() -> PassphrasePrompt {
+    PassphrasePrompt {
+        request: use_signal(|| None),
+    }
+}
+
+/// [`PassphraseDialog`] shows each request made through a
+/// [`PassphrasePrompt`].
+#[component]
+pub fn PassphraseDialog(prompt: PassphrasePrompt) -> Element {
+    let mut request = prompt.request;
+    let mut passphrase = use_signal(String::new);
+    let mut confirmation = use_signal(String::new);
+    let open = use_memo(move || request.read().is_some());
+    let choosing = use_memo(move || request.read().as_ref().is_some_and(|r| r.choosing));
+    let mismatched = use_memo(move || choosing() && passphrase() != confirmation());
+
+    let mut answer = move |answer: Option<String>| {
+        // Dropping the request without replying cancels it
+        if let (Some(request), Some(answer)) = (request.take(), answer) {
+            let _ = request.reply.send(answer);
+        }
+        passphrase.set(String::new());
+        confirmation.set(String::new());
+    };
+
+    rsx! {
+        DialogRoot {
+            class: "dialog-backdrop",
+            open: open(),
+            on_open_change: move |v| {
+                if !v {
+                    answer(None);
+                }
+            },
+            DialogContent { class: "dialog",
+                button {
+                    class: "dialog-close",
+                    aria_label: "Close",
+                    tabindex: if open() { "0" } else { "-1" },
+                    onclick: move |_| answer(None),
+                    "×"
+                }
+                if choosing() {
+                    DialogTitle { class: "dialog-title", "Encrypt File" }
+                    DialogDescription { class: "dialog-description",
+                        "Choose a passphrase to encrypt this file with, so that the names and amounts in it can't be read without it. Leave it blank to save the file without encryption."
+                    }
+                } else {
+                    DialogTitle { class: "dialog-title", "Encrypted File" }
+                    DialogDescription { class: "dialog-description",
+                        "This file is encrypted. Enter its passphrase to open it."
+                    }
+                }
+
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "passphrase", "Passphrase" }
+                    input {
+                        class: "input",
+                        id: "passphrase",
+                        r#type: "password",
+                        value: "{passphrase}",
+                        oninput: move |e| passphrase.set(e.value()),
+                    }
+                }
+                if choosing() {
+                    div { display: "flex", flex_direction: "column", gap: ".5rem",
+                        Label { class: "label", html_for: "passphrase-confirmation", "Confirm passphrase" }
+                        input {
+                            class: "input",
+                            id: "passphrase-confirmation",
+                            r#type: "password",
+                            value: "{confirmation}",
+                            oninput: move |e| confirmation.set(e.value()),
+                        }
+                    }
+                    if mismatched() {
+                        p { margin: 0, "The passphrases don't match." }
+                    } else if !passphrase().is_empty() {
+                        p { margin: 0,
+                            "Keep the passphrase safe. Without it, this file can never be opened again."
+                        }
+                    }
+                }
+
+                button {
+                    class: "button",
+                    "data-style": "primary",
+                    disabled: mismatched() || (!choosing() && passphrase().is_empty()),
+                    onclick: move |_| answer(Some(passphrase())),
+                    if choosing() {
+                        "Save"
+                    } else {
+                        "Open"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::{collections::BTreeMap, path::Path};
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::{
+    cli::{self, CliError, Command},
+    hamfest_table::{
+        export::export,
+        journal::Journal,
+        types::{Datafile, Receipt, ReceiptLine},
+    },
+};
+
+/// Run a command on a club table file. The journal alongside it is read
+/// too, but neither is changed.
+pub fn execute(command: &Command, path: &Path, passphrase: Option<&str>) -> Result<(), CliError> {
+    let (datafile, _) = Journal::read(path, passphrase).map_err(|e| CliError::Open {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    match command {
+        Command::Export { output } => {
+            let data = export(&datafile).map_err(|e| CliError::Export(e.to_string()))?;
+            cli::write_export(output, &data)
+        }
+        Command::Summary => {
+            summarise(&datafile);
+            Ok(())
+        }
+        Command::Validate => {
+            // Every receipt saved should have been paid in full
+            let unbalanced = datafile
+                .receipts()
+                .iter()
+                .filter(|r| !r.total().is_zero())
+                .inspect(|r| {
+                    println!(
+                        "Receipt {} from {} has {:.2} outstanding",
+                        r.number(),
+                        r.timestamp().format("%F %T"),
+                        r.total()
+                    );
+                })
+                .count();
+            if unbalanced == 0 {
+                println!("No problems found");
+                Ok(())
+            } else {
+                Err(CliError::ProblemsFound(unbalanced))
+            }
+        }
+        Command::Audit { .. } => Err(CliError::NoAuditLog),
+    }
+}
+
+/// Print the takings of the session, by how they were paid.
+fn summarise(datafile: &Datafile) {
+    let mut sales = BigDecimal::zero();
+    let mut takings: BTreeMap<String, BigDecimal> = BTreeMap::new();
+    for line in datafile.receipts().iter().flat_map(Receipt::lines) {
+        match line {
+            ReceiptLine::Item { item } => sales += item.price(),
+            ReceiptLine::Payment { method, amount } => {
+                *takings.entry(method.to_string()).or_default() += amount;
+            }
+            ReceiptLine::Change { method, amount } => {
+                *takings.entry(method.to_string()).or_default() -= amount;
+            }
+        }
+    }
+
+    println!("Items: {}", datafile.items().len());
+    println!("Receipts: {}", datafile.receipts().len());
+    println!("Sales: {sales:.2}");
+    for (method, amount) in takings {
+        println!("Taken by {method}: {amount:.2}");
+    }
+}
@@ -7,20 +7,21 @@ use dioxus_primitives::{
     label::Label,
 };
 
-use crate::hamfest_table::types::{Receipt, ReceiptLine, TransactionMethod};
+use crate::hamfest_table::{
+    till::Till,
+    types::{ReceiptLine, TransactionMethod},
+};
 
 #[derive(Clone, PartialEq, Props)]
 pub struct CashAndChangeDialogProps {
     open: Signal<bool>,
-    receipt: Signal<Option<Receipt>>,
+    till: Till,
 }
 
 #[component]
 pub fn CashAndChangeDialog(props: CashAndChangeDialogProps) -> Element {
-    let CashAndChangeDialogProps {
-        mut open,
-        mut receipt,
-    } = props;
+    let CashAndChangeDialogProps { mut open, till } = props;
+    let receipt = till.receipt();
 
     let total = use_memo(move || {
         if let Some(receipt) = receipt.read().as_ref() {
@@ -86,19 +87,20 @@ pub fn CashAndChangeDialog(props: CashAndChangeDialogProps) -> Element {
                         class: "button",
                         "data-style": "primary",
                         onclick: move |_| {
-                            if let Some(receipt) = receipt.write().as_mut() {
-                                receipt
-                                    .lines_mut()
-                                    .push(ReceiptLine::Payment {
-                                        method: TransactionMethod::Cash,
-                                        amount: amount_handed(),
-                                    });
-                                receipt
-                                    .lines_mut()
-                                    .push(ReceiptLine::Change {
-                                        method: TransactionMethod::Cash,
-                                        amount: change(),
-                                    });
+                            if receipt.read().is_some() {
+                                till.add_lines(
+                                    "take payment in cash".to_string(),
+                                    vec![
+                                        ReceiptLine::Payment {
+                                            method: TransactionMethod::Cash,
+                                            amount: amount_handed(),
+                                        },
+                                        ReceiptLine::Change {
+                                            method: TransactionMethod::Cash,
+                                            amount: change(),
+                                        },
+                                    ],
+                                );
                                 open.set(false);
                             }
                         },
@@ -1,17 +1,21 @@
-use std::{rc::Rc, str::FromStr};
+use std::{rc::Rc, str::FromStr, time::Duration};
 
 use bigdecimal::{BigDecimal, Zero};
 use dioxus::{logger::tracing, prelude::*};
 use dioxus_primitives::{
     label::Label,
     scroll_area::{ScrollArea, ScrollDirection},
+    toast::{use_toast, ToastOptions},
 };
 
 use crate::{
+    components::{use_history_shortcuts, HistoryShortcut},
     hamfest_table::{
         components::CashAndChangeDialog,
+        till::{Edit, Till},
         types::{Datafile, Item, Receipt, ReceiptLine, TransactionMethod},
     },
+    history::HistoryError,
     Route,
 };
 
@@ -20,21 +24,26 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
     let mut barcode = use_signal(String::new);
     let mut barcode_elem: Signal<Option<Rc<MountedData>>> = use_signal(|| None);
 
-    let mut receipt: Signal<Option<Receipt>> = use_signal(|| None);
+    let toast_api = use_toast();
+    let receipt: Signal<Option<Receipt>> = use_signal(|| None);
+    let till = use_hook(|| Till::new(datafile, receipt));
     let mut receipt_selected = use_signal(|| usize::MAX);
-    let mut ensure_receipt = move || {
-        if let Some(receipt_inner) = receipt().as_ref() {
+    let ensure_receipt = move || {
+        match receipt.peek().as_ref() {
             // If receipt is paid off, i.e. total becomes zero, save receipt and create new
-            if receipt_inner.total() == BigDecimal::zero() {
-                // Save Receipt
-                datafile.write().receipts_mut().push(receipt_inner.clone());
-                receipt.set(Some(Receipt::new()));
-                receipt_selected.set(usize::MAX);
-            }
-        } else {
+            Some(receipt) if receipt.total() == BigDecimal::zero() => vec![
+                Edit::SaveReceipt {
+                    receipt: receipt.clone(),
+                },
+                Edit::SetReceipt {
+                    receipt: Some(Receipt::new()),
+                },
+            ],
+            Some(_) => vec![],
             // If no receipt exists, create one
-            receipt.set(Some(Receipt::new()));
-            receipt_selected.set(usize::MAX);
+            None => vec![Edit::SetReceipt {
+                receipt: Some(Receipt::new()),
+            }],
         }
     };
 
@@ -73,33 +82,60 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
             return;
         }
 
-        // Create new item if it doesn't exist yet
-        if !datafile
-            .peek()
-            .items()
-            .iter()
-            .any(|i| *i.barcode() == *barcode)
-        {
-            tracing::info!("New item created!");
-            datafile
-                .write()
-                .items_mut()
-                .push(Item::new(barcode.clone()));
-        }
-        // Update item params
-        if let Some(item) = datafile
-            .write()
-            .items_mut()
-            .iter_mut()
-            .find(|i| *i.barcode() == *barcode)
-        {
-            tracing::info!("Item updated");
-            item.set_name(item_name)
-                .set_description(item_description)
-                .set_price(item_price);
-        }
+        // Create the item if it doesn't exist yet, or update its params
+        let mut saved = Item::new(barcode.clone());
+        saved
+            .set_name(item_name)
+            .set_description(item_description)
+            .set_price(item_price);
+        till.save_item(saved);
     });
 
+    let mut move_through_history = move |shortcut: HistoryShortcut| {
+        let (result, done, failed) = match shortcut {
+            HistoryShortcut::Undo => (till.undo(), "Undid", "Failed to undo"),
+            HistoryShortcut::Redo => (till.redo(), "Redid", "Failed to redo"),
+        };
+        match result {
+            Ok(description) => {
+                toast_api.info(
+                    format!("{done} {description}"),
+                    ToastOptions::new()
+                        .permanent(false)
+                        .duration(Duration::from_secs(2)),
+                );
+            }
+            Err(HistoryError::NothingToUndo | HistoryError::NothingToRedo) => return,
+            Err(e) => {
+                toast_api.error(
+                    failed.to_string(),
+                    ToastOptions::default().description(format!("{e}")),
+                );
+            }
+        }
+        // Show the item and receipt as they are now
+        receipt_selected.set(usize::MAX);
+        let barcode = barcode.peek();
+        item.set(
+            datafile
+                .peek()
+                .items()
+                .iter()
+                .find(|i| *i.barcode() == *barcode)
+                .cloned()
+                .unwrap_or_default(),
+        );
+    };
+    use_history_shortcuts(move_through_history);
+    let undo_title = till.next_undo().map_or_else(
+        || "Undo (Ctrl+Z)".to_string(),
+        |description| format!("Undo {description} (Ctrl+Z)"),
+    );
+    let redo_title = till.next_redo().map_or_else(
+        || "Redo (Ctrl+Y)".to_string(),
+        |description| format!("Redo {description} (Ctrl+Y)"),
+    );
+
     let mut cash_and_change_dialog_open = use_signal(|| false);
 
     rsx! {
@@ -112,8 +148,26 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
                 flex_direction: "column",
                 gap: "1rem",
                 flex_grow: 1,
-                Link { to: Route::Home {},
-                    button { class: "button", "data-style": "outline", "← Main Menu" }
+                div { display: "flex", gap: ".5rem",
+                    Link { to: Route::Home {},
+                        button { class: "button", "data-style": "outline", "← Main Menu" }
+                    }
+                    button {
+                        class: "button",
+                        "data-style": "outline",
+                        disabled: till.next_undo().is_none(),
+                        title: undo_title,
+                        onclick: move |_| move_through_history(HistoryShortcut::Undo),
+                        "Undo"
+                    }
+                    button {
+                        class: "button",
+                        "data-style": "outline",
+                        disabled: till.next_redo().is_none(),
+                        title: redo_title,
+                        onclick: move |_| move_through_history(HistoryShortcut::Redo),
+                        "Redo"
+                    }
                 }
                 // Search input
                 div { display: "flex", gap: ".5rem",
@@ -151,31 +205,45 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
                         onclick: move |_| async move {
                             if receipt_selected() == usize::MAX {
                                 // No receipt item selected, add item to receipt
-                                ensure_receipt();
-                                if let Some(receipt) = receipt.write().as_mut() {
-                                    // Only add to receipt if it's not already on there.
-                                    if !receipt
-                                        .lines()
-                                        .iter()
-                                        .filter_map(|l| match l {
-                                            ReceiptLine::Item { item } => Some(item),
-                                            _ => None,
-                                        })
-                                        .any(|i| *i.barcode() == barcode.read().clone())
-                                    {
-                                        receipt.lines_mut().push(ReceiptLine::Item { item: item() });
-                                    }
+                                let mut edits = ensure_receipt();
+                                let lines = match (edits.is_empty(), receipt.peek().as_ref()) {
+                                    (true, Some(receipt)) => receipt.lines().clone(),
+                                    _ => vec![],
+                                };
+                                // Only add to receipt if it's not already on there.
+                                if !lines
+                                    .iter()
+                                    .filter_map(|l| match l {
+                                        ReceiptLine::Item { item } => Some(item),
+                                        _ => None,
+                                    })
+                                    .any(|i| *i.barcode() == barcode.read().clone())
+                                {
+                                    edits
+                                        .push(Edit::InsertLine {
+                                            idx: lines.len(),
+                                            line: ReceiptLine::Item { item: item() },
+                                        });
+                                    till.make(format!("add {} to the receipt", item.read().name()), edits);
                                 }
+                                receipt_selected.set(usize::MAX);
                                 barcode.set(String::new());
                                 if let Some(elem) = barcode_elem() {
                                     _ = elem.set_focus(true).await;
                                 }
                             } else {
                                 // Receipt item selected, remove that index
-                                if let Some(receipt) = receipt.write().as_mut() {
-                                    let idx = receipt_selected();
+                                let idx = receipt_selected();
+                                let line = receipt
+                                    .read()
+                                    .as_ref()
+                                    .and_then(|r| r.lines().get(idx).map(ToString::to_string));
+                                if let Some(line) = line {
                                     tracing::info!("Removing line {idx} from receipt");
-                                    receipt.lines_mut().remove(idx);
+                                    till.make(
+                                        format!("remove {line} from the receipt"),
+                                        vec![Edit::RemoveLine { idx }],
+                                    );
                                     receipt_selected.set(usize::MAX);
                                 }
                             }
@@ -297,19 +365,22 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
                         onclick: move |_| cash_and_change_dialog_open.set(true),
                         "Cash"
                     }
-                    CashAndChangeDialog { receipt, open: cash_and_change_dialog_open }
+                    CashAndChangeDialog { till, open: cash_and_change_dialog_open }
                     button {
                         class: "button",
                         "data-style": "primary",
                         onclick: move |_| {
-                            if let Some(receipt) = receipt.write().as_mut() {
-                                let total = receipt.total();
-                                receipt
-                                    .lines_mut()
-                                    .push(ReceiptLine::Payment {
-                                        method: TransactionMethod::Card,
-                                        amount: total,
-                                    });
+                            let total = receipt.read().as_ref().map(Receipt::total);
+                            if let Some(total) = total {
+                                till.add_lines(
+                                    "take payment by card".to_string(),
+                                    vec![
+                                        ReceiptLine::Payment {
+                                            method: TransactionMethod::Card,
+                                            amount: total,
+                                        },
+                                    ],
+                                );
                             }
                         },
                         "Card"
@@ -318,14 +389,17 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
                         class: "button",
                         "data-style": "primary",
                         onclick: move |_| {
-                            if let Some(receipt) = receipt.write().as_mut() {
-                                let total = receipt.total();
-                                receipt
-                                    .lines_mut()
-                                    .push(ReceiptLine::Payment {
-                                        method: TransactionMethod::BankTransfer,
-                                        amount: total,
-                                    });
+                            let total = receipt.read().as_ref().map(Receipt::total);
+                            if let Some(total) = total {
+                                till.add_lines(
+                                    "take payment by bank transfer".to_string(),
+                                    vec![
+                                        ReceiptLine::Payment {
+                                            method: TransactionMethod::BankTransfer,
+                                            amount: total,
+                                        },
+                                    ],
+                                );
                             }
                         },
                         "Bank Transfer"
@@ -334,14 +408,17 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
                         class: "button",
                         "data-style": "primary",
                         onclick: move |_| {
-                            if let Some(receipt) = receipt.write().as_mut() {
-                                let total = receipt.total();
-                                receipt
-                                    .lines_mut()
-                                    .push(ReceiptLine::Payment {
-                                        method: TransactionMethod::Cheque,
-                                        amount: total,
-                                    });
+                            let total = receipt.read().as_ref().map(Receipt::total);
+                            if let Some(total) = total {
+                                till.add_lines(
+                                    "take payment by cheque".to_string(),
+                                    vec![
+                                        ReceiptLine::Payment {
+                                            method: TransactionMethod::Cheque,
+                                            amount: total,
+                                        },
+                                    ],
+                                );
                             }
                         },
                         "Cheque"
@@ -351,7 +428,12 @@ pub fn LoadedFile(datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>)
                         class: "button",
                         "data-style": "primary",
                         onclick: move |_| {
-                            receipt.set(None);
+                            if receipt.read().is_some() {
+                                till.make(
+                                    "void the receipt".to_string(),
+                                    vec![Edit::SetReceipt { receipt: None }],
+                                );
+                            }
                             receipt_selected.set(usize::MAX);
                         },
                         "Void Receipt"
@@ -1,8 +1,11 @@
+#[cfg(feature = "cli")]
+pub mod cli;
 mod components;
-mod export;
-mod types;
+mod till;
 mod views;
 
+use tdars_core::hamfest_table::{export, journal, types};
+
 pub mod prelude {
     pub use super::views::HamfestTable;
 }
@@ -0,0 +1,267 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    hamfest_table::types::{Datafile, Item, Receipt, ReceiptLine},
+    history::{History, HistoryError, Step},
+};
+
+/// A change made at the till, to the receipt being taken or to the items
+/// and receipts saved in the session
+#[derive(Clone, PartialEq)]
+pub enum Edit {
+    /// An item was created or its details changed
+    SaveItem { item: Item },
+    /// A new item was removed again
+    RemoveItem { barcode: String },
+    /// A completed receipt was saved to the session
+    SaveReceipt { receipt: Receipt },
+    /// A saved receipt was removed again
+    RemoveReceipt { number: Uuid },
+    /// A line was added to the receipt being taken
+    InsertLine { idx: usize, line: ReceiptLine },
+    /// A line was removed from the receipt being taken
+    RemoveLine { idx: usize },
+    /// The receipt being taken was started, voided or brought back
+    SetReceipt { receipt: Option<Receipt> },
+}
+
+impl Edit {
+    /// Whether this edit changes the session, rather than only the
+    /// receipt being taken
+    fn changes_session(&self) -> bool {
+        matches!(
+            self,
+            Self::SaveItem { .. }
+                | Self::RemoveItem { .. }
+                | Self::SaveReceipt { .. }
+                | Self::RemoveReceipt { .. }
+        )
+    }
+
+    /// Apply an edit which changes the session, returning the edit which
+    /// would undo it, or `None` if it no longer applies.
+    fn apply_to_session(self, datafile: &mut Datafile) -> Option<Self> {
+        match self {
+            Self::SaveItem { item } => {
+                let items = datafile.items_mut();
+                if let Some(existing) = items.iter_mut().find(|i| i.barcode() == item.barcode()) {
+                    return Some(Self::SaveItem {
+                        item: std::mem::replace(existing, item),
+                    });
+                }
+                let barcode = item.barcode().clone();
+                items.push(item);
+                Some(Self::RemoveItem { barcode })
+            }
+            Self::RemoveItem { barcode } => {
+                let items = datafile.items_mut();
+                let idx = items.iter().position(|i| *i.barcode() == barcode)?;
+                Some(Self::SaveItem {
+                    item: items.remove(idx),
+                })
+            }
+            Self::SaveReceipt { receipt } => {
+                let receipts = datafile.receipts_mut();
+                if receipts.iter().any(|r| r.number() == receipt.number()) {
+                    return None;
+                }
+                let number = *receipt.number();
+                receipts.push(receipt);
+                Some(Self::RemoveReceipt { number })
+            }
+            Self::RemoveReceipt { number } => {
+                let receipts = datafile.receipts_mut();
+                let idx = receipts.iter().position(|r| *r.number() == number)?;
+                Some(Self::SaveReceipt {
+                    receipt: receipts.remove(idx),
+                })
+            }
+            Self::InsertLine { .. } | Self::RemoveLine { .. } | Self::SetReceipt { .. } => None,
+        }
+    }
+
+    /// Apply an edit which changes the receipt being taken, returning the
+    /// edit which would undo it, or `None` if it no longer applies.
+    fn apply_to_receipt(self, receipt: &mut Option<Receipt>) -> Option<Self> {
+        match self {
+            Self::InsertLine { idx, line } => {
+                let lines = receipt.as_mut()?.lines_mut();
+                if idx > lines.len() {
+                    return None;
+                }
+                lines.insert(idx, line);
+                Some(Self::RemoveLine { idx })
+            }
+            Self::RemoveLine { idx } => {
+                let lines = receipt.as_mut()?.lines_mut();
+                if idx >= lines.len() {
+                    return None;
+                }
+                Some(Self::InsertLine {
+                    idx,
+                    line: lines.remove(idx),
+                })
+            }
+            Self::SetReceipt {
+                receipt: replacement,
+            } => Some(Self::SetReceipt {
+                receipt: std::mem::replace(receipt, replacement),
+            }),
+            Self::SaveItem { .. }
+            | Self::RemoveItem { .. }
+            | Self::SaveReceipt { .. }
+            | Self::RemoveReceipt { .. } => None,
+        }
+    }
+}
+
+/// The till, which every change to the open session and the receipt
+/// being taken is made through, so that it can be undone.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Till {
+    datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>,
+    receipt: Signal<Option<Receipt>>,
+    /// The changes which can be undone and redone, each made up of the
+    /// edits which reverse it
+    history: Signal<History<Vec<Edit>>>,
+}
+
+impl Till {
+    pub fn new(
+        datafile: MappedMutSignal<Datafile, Signal<Option<Datafile>>>,
+        receipt: Signal<Option<Receipt>>,
+    ) -> Self {
+        Self {
+            datafile,
+            receipt,
+            history: Signal::new(History::default()),
+        }
+    }
+
+    /// The receipt being taken, if one has been started
+    pub fn receipt(self) -> Signal<Option<Receipt>> {
+        self.receipt
+    }
+
+    /// Make a change, described for the undo history, of one or more
+    /// edits applied in order.
+    pub fn make(mut self, description: String, edits: Vec<Edit>) {
+        let inverse = self.apply(edits);
+        let mut history = self.history.write();
+        match inverse {
+            Some(inverse) => history.record(Step::new(description, inverse)),
+            None => history.clear(),
+        }
+    }
+
+    /// Add lines to the end of the receipt being taken, if one has been
+    /// started.
+    pub fn add_lines(self, description: String, lines: Vec<ReceiptLine>) {
+        let Some(len) = self.receipt.peek().as_ref().map(|r| r.lines().len()) else {
+            return;
+        };
+        let edits = lines
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| Edit::InsertLine {
+                idx: len + idx,
+                line,
+            })
+            .collect();
+        self.make(description, edits);
+    }
+
+    /// Save the details of an item as they are typed in. Changes to the
+    /// same item one after another are undone together, and saving an
+    /// item unchanged does nothing.
+    pub fn save_item(mut self, item: Item) {
+        if self.datafile.peek().items().contains(&item) {
+            return;
+        }
+        let continues_last = {
+            let history = self.history.peek();
+            history.next_redo().is_none()
+                && history.next_undo().is_some_and(|step| {
+                    matches!(
+                        step.inverse().as_slice(),
+                        [Edit::SaveItem { item: saved }] if saved.barcode() == item.barcode()
+                    ) || matches!(
+                        step.inverse().as_slice(),
+                        [Edit::RemoveItem { barcode }] if barcode == item.barcode()
+                    )
+                })
+        };
+        if continues_last {
+            // Keep undoing to how the item was before the first change
+            if self.apply(vec![Edit::SaveItem { item }]).is_none() {
+                self.history.write().clear();
+            }
+        } else {
+            let description = format!("change item {}", item.barcode());
+            self.make(description, vec![Edit::SaveItem { item }]);
+        }
+    }
+
+    /// Undo the last change made at the till. Returns the description of
+    /// the change undone.
+    pub fn undo(mut self) -> Result<String, HistoryError> {
+        let step = self.history.write().take_undo()?;
+        let Some(redo) = self.apply(step.inverse().clone()) else {
+            self.history.write().clear();
+            return Err(HistoryError::Rejected(step.description().clone()));
+        };
+        self.history
+            .write()
+            .undone(Step::new(step.description().clone(), redo));
+        Ok(step.description().clone())
+    }
+
+    /// Redo the last change undone at the till. Returns the description
+    /// of the change redone.
+    pub fn redo(mut self) -> Result<String, HistoryError> {
+        let step = self.history.write().take_redo()?;
+        let Some(undo) = self.apply(step.inverse().clone()) else {
+            self.history.write().clear();
+            return Err(HistoryError::Rejected(step.description().clone()));
+        };
+        self.history
+            .write()
+            .redone(Step::new(step.description().clone(), undo));
+        Ok(step.description().clone())
+    }
+
+    /// The description of the change [`Till::undo`] would undo
+    pub fn next_undo(self) -> Option<String> {
+        self.history
+            .read()
+            .next_undo()
+            .map(|s| s.description().clone())
+    }
+
+    /// The description of the change [`Till::redo`] would redo
+    pub fn next_redo(self) -> Option<String> {
+        self.history
+            .read()
+            .next_redo()
+            .map(|s| s.description().clone())
+    }
+
+    /// Apply edits in order, returning the edits which would undo them,
+    /// or `None` if any no longer applies.
+    ///
+    /// The session is only written to by edits which change it, as every
+    /// write to it is recorded in its journal.
+    fn apply(mut self, edits: Vec<Edit>) -> Option<Vec<Edit>> {
+        let mut inverse = vec![];
+        for edit in edits {
+            let undo = if edit.changes_session() {
+                edit.apply_to_session(&mut self.datafile.write())
+            } else {
+                edit.apply_to_receipt(&mut self.receipt.write())
+            }?;
+            inverse.insert(0, undo);
+        }
+        Some(inverse)
+    }
+}
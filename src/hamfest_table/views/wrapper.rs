@@ -1,10 +1,13 @@
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 
 use dioxus::{logger::tracing, prelude::*};
 use dioxus_primitives::toast::{use_toast, ToastOptions};
 
+use crate::components::{use_passphrase_prompt, PassphraseDialog, PassphrasePrompt};
+use crate::encryption::Key;
 use crate::hamfest_table::components::LoadedFile;
 use crate::hamfest_table::export::export;
+use crate::hamfest_table::journal::Journal;
 use crate::hamfest_table::types::Datafile;
 use crate::Route;
 
@@ -12,20 +15,15 @@ use crate::Route;
 pub fn HamfestTable() -> Element {
     let toast_api = use_toast();
     let mut datafile: Signal<Option<Datafile>> = use_signal(|| None);
-    let mut file_handle: Signal<Option<rfd::FileHandle>> = use_signal(|| None);
-    let file_open = use_memo(move || datafile.read().is_some() && file_handle.read().is_some());
+    let mut journal: Signal<Option<Journal>> = use_signal(|| None);
+    let prompt = use_passphrase_prompt();
+    let file_open = use_memo(move || datafile.read().is_some() && journal.read().is_some());
     use_effect(move || {
-        // Save file on changes
-        let handle = file_handle.peek();
+        // Record changes in the journal
         if let Some(datafile) = datafile.read().as_ref() {
-            #[allow(
-                clippy::unwrap_used,
-                reason = "the format is guaranteed to be serializable"
-            )]
-            let data = serde_json::to_vec(&datafile).unwrap();
-            if let Some(handle) = handle.clone() {
-                spawn(async move {
-                    if handle.write(&data).await.is_ok() {
+            if let Some(journal) = journal.write().as_mut() {
+                match journal.record(datafile) {
+                    Ok(()) => {
                         toast_api.success(
                             "Automatically saved".to_string(),
                             ToastOptions::new()
@@ -33,7 +31,13 @@ pub fn HamfestTable() -> Element {
                                 .duration(Duration::from_secs(2)),
                         );
                     }
-                });
+                    Err(e) => {
+                        toast_api.error(
+                            "Failed to save".to_string(),
+                            ToastOptions::default().description(format!("{e}")),
+                        );
+                    }
+                }
             }
         }
     });
@@ -61,9 +65,25 @@ pub fn HamfestTable() -> Element {
                             .save_file()
                             .await
                         {
+                            let Some(passphrase) = prompt.choose().await else {
+                                return;
+                            };
                             tracing::info!("Creating new session...");
-                            datafile.set(Some(Datafile::new()));
-                            file_handle.set(Some(handle));
+                            let new_datafile = Datafile::new();
+                            let key = (!passphrase.is_empty()).then(|| Key::new(&passphrase));
+                            match Journal::create(handle.path().to_path_buf(), &new_datafile, key) {
+                                Ok(new_journal) => {
+                                    journal.set(Some(new_journal));
+                                    datafile.set(Some(new_datafile));
+                                }
+                                Err(e) => {
+                                    toast_api
+                                        .error(
+                                            "Failed to create session".to_string(),
+                                            ToastOptions::default().description(format!("{e}")),
+                                        );
+                                }
+                            }
                         }
                     },
                     "New Session"
@@ -77,13 +97,14 @@ pub fn HamfestTable() -> Element {
                             .pick_file()
                             .await
                         {
-                            let data = handle.read().await;
-
-                            match serde_json::from_slice::<Datafile>(&data) {
-                                Ok(loaded_data) => {
+                            let Some(passphrase) = passphrase_for(prompt, handle.path()).await else {
+                                return;
+                            };
+                            match Journal::open(handle.path().to_path_buf(), passphrase.as_deref()) {
+                                Ok((loaded_journal, loaded_data)) => {
                                     tracing::info!("Loaded session");
+                                    journal.set(Some(loaded_journal));
                                     datafile.set(Some(loaded_data));
-                                    file_handle.set(Some(handle));
                                 }
                                 Err(e) => {
                                     toast_api
@@ -97,6 +118,99 @@ pub fn HamfestTable() -> Element {
                     },
                     "Open Session"
                 }
+                button {
+                    class: "fat wide button",
+                    "data-style": "outline",
+                    onclick: move |_| async move {
+                        let Some(handles) = rfd::AsyncFileDialog::new()
+                            .set_title("Select the sessions to merge")
+                            .add_filter("TDARS club table", &["tdars_club_table"])
+                            .pick_files()
+                            .await
+                        else {
+                            return;
+                        };
+                        tracing::info!("Merging sessions...");
+                        let mut merged: Option<Datafile> = None;
+                        let mut conflicts = vec![];
+                        for handle in &handles {
+                            let Some(passphrase) = passphrase_for(prompt, handle.path()).await else {
+                                return;
+                            };
+                            match Journal::read(handle.path(), passphrase.as_deref()) {
+                                Ok((loaded_data, _)) => {
+                                    merged = Some(
+                                        match merged {
+                                            None => loaded_data,
+                                            Some(merged) => {
+                                                let (merged, new_conflicts) = merged.merge(&loaded_data);
+                                                conflicts
+                                                    .extend(
+                                                        new_conflicts
+                                                            .iter()
+                                                            .map(|c| format!("{}: {c}", handle.file_name())),
+                                                    );
+                                                merged
+                                            }
+                                        },
+                                    );
+                                }
+                                Err(e) => {
+                                    toast_api
+                                        .error(
+                                            format!("Failed to load {}", handle.file_name()),
+                                            ToastOptions::default().description(format!("{e}")),
+                                        );
+                                    return;
+                                }
+                            }
+                        }
+                        let Some(merged) = merged else {
+                            return;
+                        };
+                        let Some(handle) = rfd::AsyncFileDialog::new()
+                            .set_title("Save the merged session")
+                            .add_filter("TDARS club table", &["tdars_club_table"])
+                            .set_file_name("merged.tdars_club_table")
+                            .save_file()
+                            .await
+                        else {
+                            return;
+                        };
+                        let Some(passphrase) = prompt.choose().await else {
+                            return;
+                        };
+                        let key = (!passphrase.is_empty()).then(|| Key::new(&passphrase));
+                        match Journal::create(handle.path().to_path_buf(), &merged, key) {
+                            Ok(new_journal) => {
+                                if !conflicts.is_empty() {
+                                    rfd::AsyncMessageDialog::new()
+                                        .set_title("Merge conflicts")
+                                        .set_description(
+                                            format!(
+                                                "The sessions were merged, but the following conflicts could not be resolved:\n\n{}",
+                                                conflicts.join("\n"),
+                                            ),
+                                        )
+                                        .set_level(rfd::MessageLevel::Warning)
+                                        .set_buttons(rfd::MessageButtons::Ok)
+                                        .show()
+                                        .await;
+                                }
+                                journal.set(Some(new_journal));
+                                datafile.set(Some(merged));
+                            }
+                            Err(e) => {
+                                toast_api
+                                    .error(
+                                        "Failed to save merged session".to_string(),
+                                        ToastOptions::default().description(format!("{e}")),
+                                    );
+                            }
+                        }
+                    },
+                    "Merge Sessions"
+                }
                 button {
                     class: "fat wide button",
                     "data-style": "outline",
@@ -106,10 +220,11 @@ pub fn HamfestTable() -> Element {
                             .pick_file()
                             .await
                         {
-                            let data = handle.read().await;
-
-                            match serde_json::from_slice::<Datafile>(&data) {
-                                Ok(loaded_data) => {
+                            let Some(passphrase) = passphrase_for(prompt, handle.path()).await else {
+                                return;
+                            };
+                            match Journal::read(handle.path(), passphrase.as_deref()) {
+                                Ok((loaded_data, _)) => {
                                     tracing::info!("Loaded session");
                                     match export(&loaded_data) {
                                         Err(e) => {
@@ -163,5 +278,18 @@ pub fn HamfestTable() -> Element {
                 }
             }
         }
+
+        PassphraseDialog { prompt }
+    }
+}
+
+/// Ask for the passphrase of the session at the path, if it is
+/// encrypted. Returns `None` if the user cancels.
+async fn passphrase_for(prompt: PassphrasePrompt, path: &Path) -> Option<Option<String>> {
+    // If the file can't be read, opening it reports why
+    if Journal::is_encrypted(path).unwrap_or(false) {
+        prompt.enter().await.map(Some)
+    } else {
+        Some(None)
     }
 }
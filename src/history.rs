@@ -0,0 +1,112 @@
+use derive_more::Display;
+use getset::Getters;
+
+/// The most changes kept to undo
+const UNDO_LIMIT: usize = 200;
+
+/// A change which can be undone or redone, by applying its inverse
+#[derive(Clone, Getters)]
+#[getset(get = "pub")]
+pub struct Step<T> {
+    /// What the change did, e.g. "record lot M0ABC-1"
+    description: String,
+    /// The change which reverses it
+    inverse: T,
+}
+
+impl<T> Step<T> {
+    #[must_use]
+    pub fn new(description: String, inverse: T) -> Self {
+        Self {
+            description,
+            inverse,
+        }
+    }
+}
+
+/// The reasons a change can't be undone or redone
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum HistoryError {
+    #[display("there is nothing to undo")]
+    NothingToUndo,
+    #[display("there is nothing to redo")]
+    NothingToRedo,
+    #[display("the file has changed since, so \"{_0}\" can no longer be reversed")]
+    Rejected(String),
+}
+
+/// The changes made to a datafile which can be undone, and those undone
+/// which can be redone.
+///
+/// Undoing a step applies its inverse, and the inverse of that is kept
+/// as the step to redo it, and vice versa. Making a new change forgets
+/// everything that could be redone.
+pub struct History<T> {
+    undo: Vec<Step<T>>,
+    redo: Vec<Step<T>>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Record a new change, which can be undone by the step given.
+    pub fn record(&mut self, step: Step<T>) {
+        self.redo.clear();
+        self.push_undo(step);
+    }
+
+    /// The next change that would be undone
+    #[must_use]
+    pub fn next_undo(&self) -> Option<&Step<T>> {
+        self.undo.last()
+    }
+
+    /// The next change that would be redone
+    #[must_use]
+    pub fn next_redo(&self) -> Option<&Step<T>> {
+        self.redo.last()
+    }
+
+    /// Take the next change to undo, which should then be passed back to
+    /// [`History::undone`] with the step to redo it.
+    pub fn take_undo(&mut self) -> Result<Step<T>, HistoryError> {
+        self.undo.pop().ok_or(HistoryError::NothingToUndo)
+    }
+
+    /// Take the next change to redo, which should then be passed back to
+    /// [`History::redone`] with the step to undo it again.
+    pub fn take_redo(&mut self) -> Result<Step<T>, HistoryError> {
+        self.redo.pop().ok_or(HistoryError::NothingToRedo)
+    }
+
+    /// A change was undone, and can be redone by the step given.
+    pub fn undone(&mut self, step: Step<T>) {
+        self.redo.push(step);
+    }
+
+    /// A change was redone, and can be undone again by the step given.
+    pub fn redone(&mut self, step: Step<T>) {
+        self.push_undo(step);
+    }
+
+    /// Forget every change, for example because they can no longer be
+    /// reversed in order.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn push_undo(&mut self, step: Step<T>) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(step);
+    }
+}
@@ -9,9 +9,13 @@ use dioxus::prelude::*;
 
 use hamfest_table::prelude::*;
 use surplus_sale::prelude::*;
+use tdars_core::{encryption, schema};
 use views::{Home, Navbar};
 
+#[cfg(feature = "cli")]
+mod cli;
 mod components;
+mod history;
 mod types;
 mod views;
 
@@ -37,6 +41,12 @@ enum Route {
 }
 
 fn main() {
+    // Files can be worked with from the command line, without a display
+    #[cfg(feature = "cli")]
+    if cli::requested() {
+        std::process::exit(cli::run());
+    }
+
     // The `launch` function is the main entry point for a dioxus app. It takes a component and renders it with the platform feature
     // you have enabled
     dioxus::launch(App);
@@ -0,0 +1,124 @@
+use std::{fs, path::Path};
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::{
+    cli::{self, CliError, Command},
+    encryption::{self, DecryptError},
+    surplus_sale::{export::export, replay::check_integrity, types::Datafile, validate::validate},
+};
+
+/// Run a command on a surplus sale file.
+pub fn execute(command: &Command, path: &Path, passphrase: Option<&str>) -> Result<(), CliError> {
+    let datafile = read(path, passphrase).map_err(|reason| CliError::Open {
+        path: path.to_path_buf(),
+        reason,
+    })?;
+    match command {
+        Command::Export { output } => {
+            let data = export(&datafile).map_err(|e| CliError::Export(e.to_string()))?;
+            cli::write_export(output, &data)
+        }
+        Command::Summary => {
+            summarise(&datafile);
+            Ok(())
+        }
+        Command::Validate => {
+            let mut problems = validate(&datafile)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            if let Err(e) = datafile.verify_audit_chain() {
+                problems.insert(0, format!("The audit log has been tampered with: {e}"));
+            }
+            let unchained = datafile.unchained_entries();
+            if unchained > 0 {
+                eprintln!(
+                    "warning: the first {unchained} audit log entries were written before the log was chained, so can't be verified"
+                );
+            }
+            problems.extend(check_integrity(&datafile).iter().map(ToString::to_string));
+            for problem in &problems {
+                println!("{problem}");
+            }
+            if problems.is_empty() {
+                println!("No problems found");
+                Ok(())
+            } else {
+                Err(CliError::ProblemsFound(problems.len()))
+            }
+        }
+        Command::Audit { json: true } => {
+            #[allow(
+                clippy::unwrap_used,
+                reason = "the format is guaranteed to be serializable"
+            )]
+            let json = serde_json::to_string_pretty(datafile.audit_log()).unwrap();
+            println!("{json}");
+            Ok(())
+        }
+        Command::Audit { json: false } => {
+            for entry in datafile.audit_log() {
+                println!("{entry}");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Read a surplus sale file, decrypting it with the passphrase if it is
+/// encrypted.
+fn read(path: &Path, passphrase: Option<&str>) -> Result<Datafile, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let data = if encryption::is_encrypted(&data) {
+        let passphrase = passphrase.ok_or_else(|| DecryptError::PassphraseRequired.to_string())?;
+        encryption::open(&data, passphrase)
+            .map_err(|e| e.to_string())?
+            .1
+    } else {
+        data
+    };
+    Datafile::load(&data).map_err(|e| e.to_string())
+}
+
+/// Print the totals of the sale, and what is still to be settled.
+fn summarise(datafile: &Datafile) {
+    let currency = datafile.currency();
+    let items = datafile.items();
+    let sold = items
+        .iter()
+        .filter_map(|i| i.sold_details().as_ref())
+        .collect::<Vec<_>>();
+    let hammer: BigDecimal = sold.iter().map(|s| s.hammer_price()).sum();
+    let commission: BigDecimal = sold.iter().map(|s| s.commission()).sum();
+    let unsettled = sold
+        .iter()
+        .filter(|s| s.seller_reconciled().is_none() || s.buyer_reconciled().is_none())
+        .count();
+    let owed: BigDecimal = datafile
+        .callsign_liabilities()
+        .values()
+        .filter(|l| **l > BigDecimal::zero())
+        .sum();
+    let donations: BigDecimal = datafile.club_donations().iter().map(|(_, d)| d).sum();
+
+    println!("Auction held {}", datafile.auction_date().format("%F"));
+    println!("Currency: {currency}");
+    println!("Callsigns: {}", datafile.callsigns().len());
+    println!(
+        "Lots: {} ({} sold, {} unsold)",
+        items.len(),
+        sold.len(),
+        items.len() - sold.len()
+    );
+    println!("Hammer total: {hammer:.2}");
+    println!("Commission: {commission:.2}");
+    println!("Donations: {donations:.2}");
+    println!("Lots not yet settled: {unsettled}");
+    println!(
+        "Owed to the club: {owed:.2} by {} callsign(s)",
+        datafile.debtors().len()
+    );
+    println!("Reconciliations: {}", datafile.reconciliations().len());
+    println!("Audit log entries: {}", datafile.audit_log().len());
+}
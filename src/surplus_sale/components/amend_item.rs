@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use dioxus::prelude::*;
+use dioxus_primitives::{
+    dialog::{DialogContent, DialogDescription, DialogRoot, DialogTitle},
+    label::Label,
+    toast::{use_toast, ToastOptions},
+};
+
+use crate::{
+    components::CallsignEntry,
+    surplus_sale::{
+        mutation::Mutation,
+        sync::Station,
+        types::{Datafile, Item},
+    },
+};
+
+#[derive(PartialEq, Props, Clone)]
+pub struct AmendItemProps {
+    open: Signal<bool>,
+    /// The lot to amend, as it currently stands
+    item: Item,
+}
+
+#[component]
+pub fn AmendItem(props: AmendItemProps) -> Element {
+    let toast_api = use_toast();
+    let datafile: Signal<Datafile> = use_context();
+    let station: Station = use_context();
+    let mut open = props.open;
+    let lot_number = props.item.lot_number().clone();
+
+    let mut description = use_signal(|| props.item.description().clone());
+    let mut sold = use_signal(|| props.item.sold_details().is_some());
+    let buyer = use_signal(|| {
+        props
+            .item
+            .sold_details()
+            .as_ref()
+            .map(|s| s.buyer_callsign().clone())
+            .unwrap_or_default()
+    });
+    let mut hammer_price = use_signal(|| {
+        props
+            .item
+            .sold_details()
+            .as_ref()
+            .map_or_else(BigDecimal::zero, |s| s.hammer_price().clone())
+    });
+
+    rsx! {
+        DialogRoot {
+            class: "dialog-backdrop",
+            open: open(),
+            on_open_change: move |v| open.set(v),
+            DialogContent { class: "dialog",
+                button {
+                    class: "dialog-close",
+                    aria_label: "Close",
+                    tabindex: if open() { "0" } else { "-1" },
+                    onclick: move |_| open.set(false),
+                    "×"
+                }
+                DialogTitle { class: "dialog-title", "Amend Lot {lot_number}" }
+                DialogDescription { class: "dialog-description",
+                    "Correct the details recorded for this lot. Changes are recorded in the audit log."
+                }
+
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "amend-description", "Item description" }
+
+                    input {
+                        class: "input",
+                        id: "amend-description",
+                        value: "{description}",
+                        oninput: move |e| description.set(e.value()),
+                    }
+                }
+
+                div { display: "flex", flex_direction: "row", gap: ".5rem",
+                    input {
+                        id: "amend-sold",
+                        r#type: "checkbox",
+                        checked: sold(),
+                        onchange: move |e| sold.set(e.checked()),
+                    }
+                    Label { class: "label", html_for: "amend-sold", "Item sold" }
+                }
+
+                if sold() {
+                    CallsignEntry {
+                        suggestion_source: datafile.read().callsigns().clone(),
+                        value: buyer,
+                        id_prefix: "amend-buyer-",
+                        label_prefix: "Buyer's",
+                    }
+                    div { display: "flex", flex_direction: "column", gap: ".5rem",
+                        Label { class: "label", html_for: "amend-hammer-price", "Hammer price" }
+
+                        input {
+                            class: "input",
+                            id: "amend-hammer-price",
+                            r#type: "number",
+                            step: "0.01",
+                            min: "0",
+                            value: "{hammer_price}",
+                            oninput: move |e| {
+                                if let Ok(p) = BigDecimal::from_str(&e.value()) {
+                                    hammer_price.set(p);
+                                }
+                            },
+                        }
+                    }
+                }
+
+                button {
+                    class: "button",
+                    "data-style": "primary",
+                    disabled: description().is_empty() || (sold() && buyer().callsign().is_empty()),
+                    onclick: {
+                        let lot_number = lot_number.clone();
+                        move |_| {
+                            let sale = if sold() {
+                                Some((hammer_price(), buyer()))
+                            } else {
+                                None
+                            };
+                            match station
+                                .apply(Mutation::AmendLot {
+                                    lot_number: lot_number.clone(),
+                                    description: description(),
+                                    sold: sale,
+                                })
+                            {
+                                Ok(_) => open.set(false),
+                                Err(e) => {
+                                    toast_api
+                                        .error(
+                                            "Failed to amend".to_string(),
+                                            ToastOptions::new().description(format!("{e}")),
+                                        );
+                                }
+                            }
+                        }
+                    },
+                    "Save"
+                }
+            }
+        }
+    }
+}
@@ -2,21 +2,23 @@ use std::{rc::Rc, str::FromStr};
 
 use bigdecimal::{BigDecimal, Zero};
 use dioxus::prelude::*;
-use dioxus_primitives::{label::Label, separator::Separator};
+use dioxus_primitives::{
+    label::Label,
+    separator::Separator,
+    toast::{use_toast, ToastOptions},
+};
 
 use crate::{
     components::CallsignEntry,
-    surplus_sale::{
-        types::{Datafile, Item},
-        NeedsSaving,
-    },
+    surplus_sale::{mutation::Mutation, sync::Station, types::Datafile},
     types::Callsign,
 };
 
 #[component]
 pub fn Auction() -> Element {
-    let mut datafile: Signal<Datafile> = use_context();
-    let mut needs_saving: Signal<NeedsSaving> = use_context();
+    let toast_api = use_toast();
+    let datafile: Signal<Datafile> = use_context();
+    let station: Station = use_context();
     let mut seller = use_signal(Callsign::default);
     let mut seller_callsign_elem: Signal<Option<Rc<MountedData>>> = use_signal(|| None);
     let lot_number = use_memo(move || {
@@ -44,19 +46,17 @@ pub fn Auction() -> Element {
             return;
         }
 
-        // Set file needs saving
-        needs_saving.set(NeedsSaving(true));
-
-        if sold {
-            // Save sale
-            let mut item = Item::new(lot_number(), seller(), item_description());
-            item.sold(hammer_price(), buyer());
-            datafile.write().push_item(item);
-        } else {
-            // Save not sold
-            datafile
-                .write()
-                .push_item(Item::new(lot_number(), seller(), item_description()));
+        if let Err(e) = station.apply(Mutation::RecordLot {
+            lot_number: lot_number(),
+            seller: seller(),
+            description: item_description(),
+            sold: sold.then(|| (hammer_price(), buyer())),
+        }) {
+            toast_api.error(
+                "Failed to record lot".to_string(),
+                ToastOptions::new().description(format!("{e}")),
+            );
+            return;
         }
 
         // Reset sale fields
@@ -0,0 +1,219 @@
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use dioxus::prelude::*;
+use dioxus_primitives::label::Label;
+
+use crate::{
+    surplus_sale::commission::{CommissionBand, CommissionOverride, CommissionRules},
+    types::Callsign,
+};
+
+/// [`CommissionRulesEditor`] allows the user to edit the bands, fees and
+/// per-seller overrides that make up a set of [`CommissionRules`].
+/// Rates are shown to the user as percentages.
+#[component]
+pub fn CommissionRulesEditor(value: Signal<CommissionRules>) -> Element {
+    let mut rules = value;
+
+    rsx! {
+        h4 { margin: 0, "Commission" }
+        p { margin: 0, font_size: ".8em",
+            "The band with the highest starting price at or below the hammer price applies to the whole lot."
+        }
+
+        for (idx , band) in rules.read().bands().iter().enumerate() {
+            div { display: "flex", flex_direction: "row", gap: ".5rem",
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "band-from-{idx}", "From (hammer price)" }
+                    input {
+                        class: "input",
+                        id: "band-from-{idx}",
+                        r#type: "number",
+                        min: "0",
+                        step: "0.01",
+                        value: "{band.from()}",
+                        oninput: move |e| {
+                            if let Ok(from) = BigDecimal::from_str(&e.value()) {
+                                if let Some(band) = rules.write().bands_mut().get_mut(idx) {
+                                    band.set_from(from);
+                                }
+                            }
+                        },
+                    }
+                }
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "band-rate-{idx}", "Rate (Percentage)" }
+                    input {
+                        class: "input",
+                        id: "band-rate-{idx}",
+                        r#type: "number",
+                        min: "0",
+                        max: "100",
+                        step: "0.1",
+                        value: "{(band.rate() * 100)}",
+                        oninput: move |e| {
+                            if let Ok(pct) = BigDecimal::from_str(&e.value()) {
+                                if let Some(band) = rules.write().bands_mut().get_mut(idx) {
+                                    band.set_rate(pct / 100);
+                                }
+                            }
+                        },
+                    }
+                }
+                div { align_content: "end",
+                    button {
+                        class: "button",
+                        "data-style": "destructive",
+                        onclick: move |_| {
+                            rules.write().bands_mut().remove(idx);
+                        },
+                        "Remove"
+                    }
+                }
+            }
+        }
+        button {
+            class: "button",
+            "data-style": "secondary",
+            onclick: move |_| {
+                rules
+                    .write()
+                    .bands_mut()
+                    .push(CommissionBand::new(BigDecimal::zero(), BigDecimal::zero()));
+            },
+            "Add Band"
+        }
+
+        div { display: "flex", flex_direction: "row", gap: ".5rem",
+            div { display: "flex", flex_direction: "column", gap: ".5rem",
+                Label { class: "label", html_for: "minimum-fee", "Minimum fee" }
+                input {
+                    class: "input",
+                    id: "minimum-fee",
+                    r#type: "number",
+                    min: "0",
+                    step: "0.01",
+                    placeholder: "None",
+                    value: rules.read().minimum_fee().as_ref().map(ToString::to_string).unwrap_or_default(),
+                    oninput: move |e| {
+                        rules.write().set_minimum_fee(BigDecimal::from_str(&e.value()).ok());
+                    },
+                }
+            }
+            div { display: "flex", flex_direction: "column", gap: ".5rem",
+                Label { class: "label", html_for: "maximum-fee", "Maximum fee" }
+                input {
+                    class: "input",
+                    id: "maximum-fee",
+                    r#type: "number",
+                    min: "0",
+                    step: "0.01",
+                    placeholder: "None",
+                    value: rules.read().maximum_fee().as_ref().map(ToString::to_string).unwrap_or_default(),
+                    oninput: move |e| {
+                        rules.write().set_maximum_fee(BigDecimal::from_str(&e.value()).ok());
+                    },
+                }
+            }
+            div { display: "flex", flex_direction: "column", gap: ".5rem",
+                Label { class: "label", html_for: "entry-fee", "Entry fee per lot" }
+                input {
+                    class: "input",
+                    id: "entry-fee",
+                    r#type: "number",
+                    min: "0",
+                    step: "0.01",
+                    value: "{rules.read().entry_fee()}",
+                    oninput: move |e| {
+                        if let Ok(fee) = BigDecimal::from_str(&e.value()) {
+                            rules.write().set_entry_fee(fee);
+                        }
+                    },
+                }
+            }
+        }
+
+        h4 { margin: 0, "Seller Overrides" }
+        for (idx , (callsign , over)) in rules.read().overrides().iter().enumerate() {
+            div { display: "flex", flex_direction: "row", gap: ".5rem",
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "override-callsign-{idx}", "Callsign" }
+                    input {
+                        class: "input",
+                        id: "override-callsign-{idx}",
+                        style: "width: 8em",
+                        placeholder: "M0ABC",
+                        value: "{callsign.callsign()}",
+                        oninput: move |e| {
+                            if let Some((cs, _)) = rules.write().overrides_mut().get_mut(idx) {
+                                cs.set_callsign(e.value().trim().to_ascii_uppercase());
+                            }
+                        },
+                    }
+                }
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "override-rate-{idx}", "Rate (Percentage)" }
+                    input {
+                        class: "input",
+                        id: "override-rate-{idx}",
+                        r#type: "number",
+                        min: "0",
+                        max: "100",
+                        step: "0.1",
+                        disabled: *over == CommissionOverride::Waived,
+                        value: match over {
+                            CommissionOverride::Rate(rate) => (rate * 100).to_string(),
+                            CommissionOverride::Waived => String::new(),
+                        },
+                        oninput: move |e| {
+                            if let Ok(pct) = BigDecimal::from_str(&e.value()) {
+                                if let Some((_, over)) = rules.write().overrides_mut().get_mut(idx) {
+                                    *over = CommissionOverride::Rate(pct / 100);
+                                }
+                            }
+                        },
+                    }
+                }
+                div { display: "flex", flex_direction: "row", gap: ".5rem", align_items: "end",
+                    input {
+                        id: "override-waived-{idx}",
+                        r#type: "checkbox",
+                        checked: *over == CommissionOverride::Waived,
+                        onchange: move |e| {
+                            if let Some((_, over)) = rules.write().overrides_mut().get_mut(idx) {
+                                *over = if e.checked() {
+                                    CommissionOverride::Waived
+                                } else {
+                                    CommissionOverride::Rate(BigDecimal::zero())
+                                };
+                            }
+                        },
+                    }
+                    Label { class: "label", html_for: "override-waived-{idx}", "Waived" }
+                }
+                div { align_content: "end",
+                    button {
+                        class: "button",
+                        "data-style": "destructive",
+                        onclick: move |_| {
+                            rules.write().overrides_mut().remove(idx);
+                        },
+                        "Remove"
+                    }
+                }
+            }
+        }
+        button {
+            class: "button",
+            "data-style": "secondary",
+            onclick: move |_| {
+                rules
+                    .write()
+                    .overrides_mut()
+                    .push((Callsign::default(), CommissionOverride::Waived));
+            },
+            "Add Override"
+        }
+    }
+}
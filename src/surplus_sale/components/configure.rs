@@ -1,6 +1,3 @@
-use std::str::FromStr;
-
-use bigdecimal::BigDecimal;
 use dioxus::prelude::*;
 use dioxus_primitives::{
     dialog::{DialogContent, DialogDescription, DialogRoot, DialogTitle},
@@ -8,13 +5,15 @@ use dioxus_primitives::{
 };
 use iso_currency::Currency;
 
-use crate::surplus_sale::types::Datafile;
+use crate::surplus_sale::{
+    commission::CommissionRules, components::CommissionRulesEditor, types::Datafile,
+};
 
 pub struct ConfigurationUpdateData {
     /// The currency used
     pub currency: Currency,
-    /// The decimal value of the club taking
-    pub club_taking: BigDecimal,
+    /// The rules for the commission the club takes
+    pub commission_rules: CommissionRules,
     /// The USB vendor ID of the ESC/POS device to use
     #[cfg(feature = "escpos")]
     pub escpos_vendor: u16,
@@ -39,8 +38,7 @@ pub fn Configure(props: ConfigureProps) -> Element {
     #[cfg(feature = "escpos")]
     let mut escpos_device = use_signal(|| 0x0000);
 
-    let mut club_taking = use_signal(|| props.datafile.read().club_taking().clone() * 100);
-    let mut club_taking_warning = use_signal(|| false);
+    let commission_rules = use_signal(|| props.datafile.read().commission_rules().clone());
 
     rsx! {
         DialogRoot {
@@ -58,35 +56,6 @@ pub fn Configure(props: ConfigureProps) -> Element {
                 DialogTitle { class: "dialog-title", "Configure Surplus Sale" }
                 DialogDescription { class: "dialog-description", "Configure the surplus sale's parameters" }
 
-                div { display: "flex", flex_direction: "column", gap: ".5rem",
-                    Label { class: "label", html_for: "club-percentage", "Club Takings (Percentage)" }
-
-                    input {
-                        class: "input",
-                        r#type: "number",
-                        min: "0",
-                        step: "0.1",
-                        max: "100",
-                        id: "club-percentage",
-                        value: "{club_taking}",
-                        oninput: move |e| {
-                            if let Ok(taking) = BigDecimal::from_str(&e.value()) {
-                                club_taking.set(taking);
-                                club_taking_warning.set(false);
-                            } else {
-                                club_taking_warning.set(true);
-                            }
-                        },
-                        placeholder: "10",
-                    }
-                    if club_taking_warning() {
-                        p { font_size: ".5em", margin_top: 0,
-
-                            "The value you have entered is invalid and will be ignored!"
-                        }
-                    }
-                }
-
                 div { display: "flex", flex_direction: "column", gap: ".5rem",
                     Label { class: "label", html_for: "currency", "Currency" }
 
@@ -106,6 +75,8 @@ pub fn Configure(props: ConfigureProps) -> Element {
                     }
                 }
 
+                CommissionRulesEditor { value: commission_rules }
+
                 if cfg!(feature = "escpos") {
                     ESCPOSConfigurator {
                         on_ids_changed: move |(vid, did)| {
@@ -126,7 +97,7 @@ pub fn Configure(props: ConfigureProps) -> Element {
                     onclick: move |_| {
                         let data = ConfigurationUpdateData {
                             currency: currency(),
-                            club_taking: club_taking() / 100,
+                            commission_rules: commission_rules(),
                             #[cfg(feature = "escpos")]
                             escpos_vendor: escpos_vendor(),
                             #[cfg(feature = "escpos")]
@@ -0,0 +1,116 @@
+use bigdecimal::Zero;
+use chrono::Local;
+use dioxus::prelude::*;
+use dioxus_primitives::toast::{use_toast, ToastOptions};
+
+use crate::surplus_sale::{
+    mutation::Mutation,
+    sync::Station,
+    types::{Datafile, ReconcileMethod, Tender},
+};
+
+/// [`Debtors`] lists every callsign that still owes the club money, so
+/// that postponed payments can be followed up and collected.
+#[component]
+pub fn Debtors() -> Element {
+    let toast_api = use_toast();
+    let datafile: Signal<Datafile> = use_context();
+    let station: Station = use_context();
+    let sym = use_memo(move || datafile.read().currency().symbol());
+    let debtors = use_memo(move || datafile.read().debtors());
+
+    let mut collect = move |debtor: usize, method: ReconcileMethod| {
+        let Some(debtor) = debtors.read().get(debtor).cloned() else {
+            return;
+        };
+        match station.apply(Mutation::CollectDebt {
+            callsign: debtor.callsign().clone(),
+            tenders: vec![Tender::new(method, debtor.amount().clone())],
+        }) {
+            Ok(change) if !change.is_zero() => {
+                toast_api.info(
+                    format!("Change for {}", debtor.callsign()),
+                    ToastOptions::new().description(format!("{change:0.02} to be given back")),
+                );
+            }
+            Ok(_) => {}
+            Err(e) => {
+                toast_api.error(
+                    "Failed to collect".to_string(),
+                    ToastOptions::new().description(format!("{e}")),
+                );
+            }
+        }
+    };
+
+    rsx! {
+        table { class: "table",
+            thead {
+                tr {
+                    th { "Callsign" }
+                    th { "Name" }
+                    th { "Owed" }
+                    th { "Age" }
+                    th { "Postponed lots" }
+                    th { "Collect" }
+                }
+            }
+            tbody {
+                if debtors.read().is_empty() {
+                    tr {
+                        td { colspan: 6, "Nobody owes the club anything." }
+                    }
+                }
+                for (idx , debtor) in debtors.read().iter().enumerate() {
+                    tr { key: "{debtor.callsign().callsign()}",
+                        td { "{debtor.callsign().callsign()}" }
+                        td { "{debtor.callsign().name()}" }
+                        td { "{sym} {debtor.amount():0.02}" }
+                        td {
+                            if let Some(since) = debtor.since() {
+                                "{(Local::now() - *since).num_days()} days"
+                            } else {
+                                "Unknown"
+                            }
+                        }
+                        td {
+                            for item in debtor.lots() {
+                                div { key: "{item.lot_number()}",
+                                    "{item.lot_number()}: {item.description()}"
+                                }
+                            }
+                        }
+                        td {
+                            div { display: "flex", flex_direction: "row", gap: ".5rem",
+                                button {
+                                    class: "button",
+                                    "data-style": "primary",
+                                    onclick: move |_| collect(idx, ReconcileMethod::Cash),
+                                    "Cash"
+                                }
+                                button {
+                                    class: "button",
+                                    "data-style": "primary",
+                                    onclick: move |_| collect(idx, ReconcileMethod::Card { reference: None }),
+                                    "Card"
+                                }
+                                button {
+                                    class: "button",
+                                    "data-style": "primary",
+                                    onclick: move |_| collect(idx, ReconcileMethod::BankTransfer { seen: true }),
+                                    "Bank Transfer (Seen)"
+                                }
+                                button {
+                                    class: "button",
+                                    "data-style": "secondary",
+                                    onclick: move |_| collect(idx, ReconcileMethod::BankTransfer { seen: false }),
+                                    "Bank Transfer (Unseen)"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use dioxus::prelude::*;
+use dioxus_primitives::{
+    dialog::{DialogContent, DialogDescription, DialogRoot, DialogTitle},
+    label::Label,
+};
+
+use crate::surplus_sale::sync::DEFAULT_PORT;
+
+#[derive(PartialEq, Props, Clone)]
+pub struct JoinStationProps {
+    open: Signal<bool>,
+    /// Called with the address of the host to join, and the PIN it shows
+    on_join: EventHandler<(String, String)>,
+}
+
+/// [`JoinStation`] asks for the address of another station hosting the
+/// surplus sale, and the PIN it shows, so that this station can join it.
+#[component]
+pub fn JoinStation(props: JoinStationProps) -> Element {
+    let mut open = props.open;
+    let mut address = use_signal(String::new);
+    let mut pin = use_signal(String::new);
+
+    rsx! {
+        DialogRoot {
+            class: "dialog-backdrop",
+            open: open(),
+            on_open_change: move |v| open.set(v),
+            DialogContent { class: "dialog",
+                button {
+                    class: "dialog-close",
+                    aria_label: "Close",
+                    tabindex: if open() { "0" } else { "-1" },
+                    onclick: move |_| open.set(false),
+                    "×"
+                }
+                DialogTitle { class: "dialog-title", "Join Station" }
+                DialogDescription { class: "dialog-description",
+                    "Share the surplus sale hosted by another station on this network. The file open here will be replaced by the host's."
+                }
+
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "host-address", "Host address" }
+
+                    input {
+                        class: "input",
+                        id: "host-address",
+                        value: "{address}",
+                        oninput: move |e| address.set(e.value()),
+                        placeholder: "e.g. 192.168.1.20 or 192.168.1.20:{DEFAULT_PORT}",
+                    }
+
+                    Label { class: "label", html_for: "host-pin", "PIN shown by the host" }
+
+                    input {
+                        class: "input",
+                        id: "host-pin",
+                        inputmode: "numeric",
+                        value: "{pin}",
+                        oninput: move |e| pin.set(e.value()),
+                    }
+                }
+
+                button {
+                    class: "button",
+                    "data-style": "primary",
+                    disabled: address().trim().is_empty() || pin().trim().is_empty(),
+                    onclick: move |_| {
+                        let host = (address().trim().to_string(), pin().trim().to_string());
+                        props.on_join.call(host);
+                        open.set(false);
+                    },
+                    "Join"
+                }
+            }
+        }
+    }
+}
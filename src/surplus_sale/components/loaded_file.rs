@@ -3,11 +3,12 @@ use dioxus_primitives::tabs::{TabContent, TabList, TabTrigger, Tabs};
 
 use crate::surplus_sale::{
     components::{
-        configure::ConfigurationUpdateData, Auction, AuditLog, Configure, Reconciliation,
+        configure::ConfigurationUpdateData, Auction, AuditLog, Configure, Debtors, Reconciliation,
         SalesOverview,
     },
+    mutation::Mutation,
+    sync::Station,
     types::Datafile,
-    NeedsSaving,
 };
 #[cfg(feature = "escpos")]
 use crate::types::ESCPOSDevice;
@@ -20,10 +21,10 @@ pub struct LoadedFileProps {
 
 #[component]
 pub fn LoadedFile(props: LoadedFileProps) -> Element {
-    let mut datafile: Signal<Datafile> = use_context_provider(|| props.loaded_file);
+    let datafile: Signal<Datafile> = use_context_provider(|| props.loaded_file);
     #[cfg(feature = "escpos")]
     let mut escpos_device = use_context_provider(|| Signal::new(ESCPOSDevice(0x0000, 0x0000)));
-    let mut needs_saving: Signal<NeedsSaving> = use_context();
+    let station: Station = use_context();
     let configure_open = props.configure_open;
 
     rsx! {
@@ -57,6 +58,12 @@ pub fn LoadedFile(props: LoadedFileProps) -> Element {
                     value: "sales".to_string(),
                     "Sales Overview"
                 }
+                TabTrigger {
+                    class: "tabs-trigger",
+                    index: 4usize,
+                    value: "debtors".to_string(),
+                    "Debtors"
+                }
             }
 
             TabContent {
@@ -87,19 +94,30 @@ pub fn LoadedFile(props: LoadedFileProps) -> Element {
 
                 SalesOverview {}
             }
+            TabContent {
+                // class: "tabs-content",
+                index: 4usize,
+                value: "debtors".to_string(),
+
+                Debtors {}
+            }
         }
 
         Configure {
             open: configure_open,
             on_update: move |data: ConfigurationUpdateData| {
-                datafile.write().set_currency(data.currency).set_club_taking(data.club_taking);
+                // Changing the configuration is never rejected
+                _ = station
+                    .apply(Mutation::Configure {
+                        currency: data.currency,
+                        commission_rules: data.commission_rules,
+                    });
                 #[cfg(feature = "escpos")]
                 {
                     // deal with ESCPOD vendor and device
                     escpos_device.write().0 = data.escpos_vendor;
                     escpos_device.write().1 = data.escpos_device;
                 }
-                needs_saving.set(NeedsSaving(true));
             },
             datafile,
         }
@@ -1,3 +1,6 @@
+mod commission_rules;
+pub use commission_rules::CommissionRulesEditor;
+
 mod configure;
 pub use configure::Configure;
 
@@ -13,5 +16,20 @@ pub use auction::Auction;
 mod reconciliation;
 pub use reconciliation::Reconciliation;
 
+mod amend_item;
+pub use amend_item::AmendItem;
+
+mod report;
+pub use report::{Report, ReportDialog};
+
+mod debtors;
+pub use debtors::Debtors;
+
 mod sales_overview;
 pub use sales_overview::SalesOverview;
+
+mod join_station;
+pub use join_station::JoinStation;
+
+mod phone_entry;
+pub use phone_entry::PhoneEntryDialog;
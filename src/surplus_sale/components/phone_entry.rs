@@ -0,0 +1,156 @@
+#[cfg(feature = "phone-entry")]
+use dioxus::logger::tracing;
+use dioxus::prelude::*;
+#[cfg(feature = "phone-entry")]
+use dioxus_primitives::{
+    dialog::{DialogContent, DialogDescription, DialogRoot, DialogTitle},
+    toast::{use_toast, ToastOptions},
+};
+
+#[cfg(feature = "phone-entry")]
+use crate::{
+    surplus_sale::{
+        mutation::Mutation,
+        phone_entry::{PhoneEntry, PhoneLot, DEFAULT_PORT},
+        sync::Station,
+    },
+    types::Callsign,
+};
+
+#[derive(PartialEq, Props, Clone)]
+pub struct PhoneEntryDialogProps {
+    open: Signal<bool>,
+}
+
+#[cfg(not(feature = "phone-entry"))]
+#[component]
+pub fn PhoneEntryDialog(props: PhoneEntryDialogProps) -> Element {
+    let _ = props;
+    rsx! {}
+}
+
+/// [`PhoneEntryDialog`] starts and stops the web server for entering lots
+/// from phones, and shows the QR code and PIN to pair them with.
+#[cfg(feature = "phone-entry")]
+#[component]
+pub fn PhoneEntryDialog(props: PhoneEntryDialogProps) -> Element {
+    let toast_api = use_toast();
+    let station: Station = use_context();
+    let mut open = props.open;
+    let mut server: Signal<Option<PhoneEntry>> = use_signal(|| None);
+
+    // Record each lot as it is entered, just as the auction tab does
+    let record = move |lot: PhoneLot| {
+        let datafile = station.datafile();
+        let known = |callsign: &Callsign| {
+            if !callsign.name().is_empty() {
+                return callsign.clone();
+            }
+            datafile
+                .read()
+                .callsigns()
+                .iter()
+                .find(|c| *c == callsign)
+                .cloned()
+                .unwrap_or_else(|| callsign.clone())
+        };
+        let seller = known(&lot.seller);
+        let sold = lot
+            .sold
+            .as_ref()
+            .map(|(hammer_price, buyer)| (hammer_price.clone(), known(buyer)));
+        let lot_number = format!(
+            "{}-{}",
+            seller.callsign(),
+            datafile.read().next_lot_number_for(&seller)
+        );
+        let recorded = station.apply(Mutation::RecordLot {
+            lot_number: lot_number.clone(),
+            seller,
+            description: lot.description.clone(),
+            sold,
+        });
+        match &recorded {
+            Ok(_) => tracing::info!("Lot {lot_number} entered from a phone"),
+            Err(e) => tracing::warn!("Lot {lot_number} from a phone was rejected: {e}"),
+        }
+        lot.reply(recorded.map(|_| lot_number).map_err(|e| e.to_string()));
+    };
+
+    let start = move |_| async move {
+        match PhoneEntry::start(DEFAULT_PORT).await {
+            Ok((started, mut lots)) => {
+                tracing::info!("Serving phone entry at {}", started.url());
+                server.set(Some(started));
+                spawn(async move {
+                    while let Some(lot) = lots.recv().await {
+                        record(lot);
+                    }
+                });
+            }
+            Err(e) => {
+                toast_api.error(
+                    "Failed to start phone entry".to_string(),
+                    ToastOptions::new().description(format!("{e}")),
+                );
+            }
+        }
+    };
+
+    rsx! {
+        DialogRoot {
+            class: "dialog-backdrop",
+            open: open(),
+            on_open_change: move |v| open.set(v),
+            DialogContent { class: "dialog",
+                button {
+                    class: "dialog-close",
+                    aria_label: "Close",
+                    tabindex: if open() { "0" } else { "-1" },
+                    onclick: move |_| open.set(false),
+                    "×"
+                }
+                DialogTitle { class: "dialog-title", "Phone Entry" }
+                DialogDescription { class: "dialog-description",
+                    "Runners on the auction floor can record lots from their phones, on the same network as this computer."
+                }
+
+                if let Some(running) = server.read().as_ref() {
+                    div {
+                        display: "flex",
+                        flex_direction: "column",
+                        align_items: "center",
+                        gap: ".5rem",
+                        div { background: "white", dangerous_inner_html: running.qr_code() }
+                        p { margin: 0, "Scan the code, or go to " }
+                        p { class: "select", margin: 0, font_family: "monospace", "{running.url()}" }
+                        p { margin: 0, "and enter the PIN" }
+                        p {
+                            class: "select",
+                            margin: 0,
+                            font_size: "2rem",
+                            font_family: "monospace",
+                            "{running.pin()}"
+                        }
+                    }
+                    button {
+                        class: "button",
+                        "data-style": "destructive",
+                        onclick: move |_| {
+                            tracing::info!("Stopping phone entry");
+                            server.set(None);
+                        },
+                        "Stop"
+                    }
+                } else {
+                    button {
+                        class: "button",
+                        "data-style": "primary",
+                        onclick: start,
+                        "Start"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -15,8 +15,9 @@ use crate::surplus_sale::types::Item;
 use crate::{
     components::CallsignEntry,
     surplus_sale::{
-        types::{Datafile, ReconcileMethod},
-        NeedsSaving,
+        mutation::Mutation,
+        sync::Station,
+        types::{display_tenders, Datafile, MutationError, ReconcileMethod, Tender},
     },
     types::Callsign,
 };
@@ -24,8 +25,8 @@ use crate::{
 #[component]
 pub fn Reconciliation() -> Element {
     let toast_api = use_toast();
-    let mut datafile: Signal<Datafile> = use_context();
-    let mut needs_saving: Signal<NeedsSaving> = use_context();
+    let datafile: Signal<Datafile> = use_context();
+    let station: Station = use_context();
     let callsign = use_signal(Callsign::default);
     let mut reconcile_amount = use_signal(BigDecimal::zero);
 
@@ -80,27 +81,81 @@ pub fn Reconciliation() -> Element {
         }
         for item in &items_sold() {
             if let Some(sold) = item.sold_details() {
-                total -= sold.hammer_price() * (1 - datafile().club_taking());
+                total -= sold.seller_proceeds();
             }
         }
         total
     });
     use_effect(move || reconcile_amount.set(total().abs()));
+    let last_reconciliation = use_memo(move || {
+        datafile
+            .read()
+            .last_reconciliation_for(&callsign())
+            .cloned()
+    });
 
-    let mut reconcile = move |method| {
-        let amt = if total() < BigDecimal::zero() {
+    // The amount to reconcile, signed by whether the club pays out
+    let signed_amount = move || {
+        if total() < BigDecimal::zero() {
             -reconcile_amount()
         } else {
             reconcile_amount()
-        };
-        let change = datafile.write().reconcile(&callsign(), amt, method);
+        }
+    };
+
+    let show_change = move |change: BigDecimal| {
         if !change.is_zero() {
             toast_api.info(
                 format!("Change for {callsign}"),
                 ToastOptions::new().description(format!("{change:0.02} to be given back")),
             );
         }
-        needs_saving.set(NeedsSaving(true));
+    };
+    let show_error = move |e: MutationError| {
+        toast_api.error(
+            "Failed to reconcile".to_string(),
+            ToastOptions::new().description(format!("{e}")),
+        );
+    };
+
+    let mut reconcile = move |method| match station.apply(Mutation::Reconcile {
+        callsign: callsign(),
+        amount: signed_amount(),
+        method,
+    }) {
+        Ok(change) => show_change(change),
+        Err(e) => show_error(e),
+    };
+
+    let mut card_reference = use_signal(String::new);
+    let card_method = move || ReconcileMethod::Card {
+        reference: Some(card_reference().trim().to_string()).filter(|r| !r.is_empty()),
+    };
+
+    let mut tender_method = use_signal(String::new);
+    let mut tenders: Signal<Vec<Tender>> = use_signal(Vec::new);
+    use_effect(move || {
+        // Pending tenders belong to the callsign they were entered for
+        let _ = callsign();
+        tenders.set(vec![]);
+    });
+    let tendered = use_memo(move || {
+        tenders
+            .read()
+            .iter()
+            .map(|t| t.amount().abs())
+            .sum::<BigDecimal>()
+    });
+
+    let mut settle = move || match station.apply(Mutation::Settle {
+        callsign: callsign(),
+        tenders: tenders(),
+    }) {
+        Ok(change) => {
+            show_change(change);
+            tenders.set(vec![]);
+        }
+        Err(e) => show_error(e),
     };
 
     rsx! {
@@ -137,6 +192,29 @@ pub fn Reconciliation() -> Element {
                         "Cash"
                     }
                 }
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "card-reference", "Card Reference" }
+                    input {
+                        class: "input",
+                        id: "card-reference",
+                        placeholder: "Optional",
+                        style: "width: 8em",
+                        value: "{card_reference}",
+                        oninput: move |e| card_reference.set(e.value()),
+                    }
+                }
+                div { align_content: "end",
+                    button {
+                        class: "button",
+                        disabled: total() == BigDecimal::zero(),
+                        "data-style": "primary",
+                        onclick: move |_| {
+                            reconcile(card_method());
+                            card_reference.set(String::new());
+                        },
+                        "Card"
+                    }
+                }
                 div { align_content: "end", margin_left: ".4rem",
                     button {
                         class: "button",
@@ -183,6 +261,96 @@ pub fn Reconciliation() -> Element {
                 }
             }
 
+            div { display: "flex", flex_direction: "row", gap: ".6rem",
+                div { display: "flex", flex_direction: "column", gap: ".5rem",
+                    Label { class: "label", html_for: "tender-method", "Split Tender Method" }
+                    select {
+                        class: "input",
+                        id: "tender-method",
+                        onchange: move |e| tender_method.set(e.value()),
+                        option { value: "cash", "Cash" }
+                        option { value: "card", "Card" }
+                        option { value: "bank-seen", "Bank Transfer (Seen)" }
+                        option { value: "bank-unseen", "Bank Transfer (Unseen)" }
+                        option { value: "postpone", "Postponed Payment" }
+                    }
+                }
+                div { align_content: "end",
+                    button {
+                        class: "button",
+                        disabled: total() == BigDecimal::zero() || reconcile_amount().is_zero(),
+                        "data-style": "secondary",
+                        onclick: move |_| {
+                            let method = if tender_method() == "card" {
+                                card_method()
+                            } else {
+                                tender_method_from_key(&tender_method())
+                            };
+                            tenders.write().push(Tender::new(method, signed_amount()));
+                        },
+                        "Add Tender"
+                    }
+                }
+                if !tenders.read().is_empty() {
+                    p { margin: 0, align_content: "end",
+                        "Tendered {sym} {tendered():0.02}: {display_tenders(&tenders.read())}"
+                    }
+                    div { align_content: "end",
+                        button {
+                            class: "button",
+                            "data-style": "outline",
+                            onclick: move |_| tenders.set(vec![]),
+                            "Clear"
+                        }
+                    }
+                    div { align_content: "end",
+                        button {
+                            class: "button",
+                            "data-style": "primary",
+                            onclick: move |_| settle(),
+                            "Settle"
+                        }
+                    }
+                }
+            }
+
+            if let Some(last) = last_reconciliation() {
+                div { display: "flex", flex_direction: "row", gap: ".6rem",
+                    p { margin: 0, align_content: "center",
+                        "Last reconciled {sym} {last.amount():0.02} via {display_tenders(last.tenders())}."
+                    }
+                    button {
+                        class: "button",
+                        "data-style": "destructive",
+                        onclick: {
+                            let audit_id = *last.audit_id();
+                            move |_| async move {
+                                let response = rfd::AsyncMessageDialog::new()
+                                    .set_title("Reverse reconciliation")
+                                    .set_description(
+                                        "This will undo the last reconciliation for this individual. Do you want to continue?",
+                                    )
+                                    .set_level(rfd::MessageLevel::Warning)
+                                    .set_buttons(rfd::MessageButtons::YesNo)
+                                    .show()
+                                    .await;
+                                if response != rfd::MessageDialogResult::Yes {
+                                    return;
+                                }
+                                if let Err(e) = station.apply(Mutation::Unreconcile { audit_id }) {
+                                    toast_api
+                                        .error(
+                                            "Failed to reverse reconciliation".to_string(),
+                                            ToastOptions::new().description(format!("{e}")),
+                                        );
+                                }
+                            }
+                        },
+                        "Reverse Last Reconciliation"
+                    }
+                }
+            }
+
             p { margin: 0,
                 "This individual "
                 match total().cmp(&BigDecimal::zero()) {
@@ -207,7 +375,6 @@ pub fn Reconciliation() -> Element {
                                 liability.read().as_ref(),
                                 items_sold.read().as_ref(),
                                 items_bought.read().as_ref(),
-                                datafile.read().club_taking(),
                             ) {
                                 Ok(()) => {
                                     toast_api
@@ -283,15 +450,15 @@ pub fn Reconciliation() -> Element {
                         if let Some(sold) = item.sold_details() {
                             tr {
                                 td { colspan: 2, text_align: "right",
-                                    em { "less club taking:" }
+                                    em { "less commission:" }
                                 }
                                 td {
                                     em {
-                                        "-{sym} {(sold.hammer_price() * datafile().club_taking()):0.02}"
+                                        "-{sym} {sold.commission():0.02}"
                                     }
                                 }
                                 td {
-                                    "{sym} {(sold.hammer_price() * (1 - datafile().club_taking())):0.02}"
+                                    "{sym} {sold.seller_proceeds():0.02}"
                                 }
                             }
                         }
@@ -302,6 +469,17 @@ pub fn Reconciliation() -> Element {
     }
 }
 
+/// Translate the value of an option in the split tender method select.
+/// Card tenders are handled separately, as they carry a reference.
+fn tender_method_from_key(key: &str) -> ReconcileMethod {
+    match key {
+        "bank-seen" => ReconcileMethod::BankTransfer { seen: true },
+        "bank-unseen" => ReconcileMethod::BankTransfer { seen: false },
+        "postpone" => ReconcileMethod::Postpone,
+        _ => ReconcileMethod::Cash,
+    }
+}
+
 #[cfg(feature = "escpos")]
 fn print_receipt(
     device: crate::types::ESCPOSDevice,
@@ -309,7 +487,6 @@ fn print_receipt(
     liability: Option<&BigDecimal>,
     sold: &Vec<Item>,
     bought: &Vec<Item>,
-    club_taking: &BigDecimal,
 ) -> escpos::errors::Result<()> {
     use escpos::{
         driver::UsbDriver,
@@ -360,15 +537,15 @@ fn print_receipt(
 
     for item in sold {
         if let Some(sold) = item.sold_details() {
-            grand_total -= sold.hammer_price() * (1 - club_taking);
+            grand_total -= sold.seller_proceeds();
             prn.justify(JustifyMode::LEFT)?
                 .writeln(item.description())?
                 .justify(JustifyMode::RIGHT)?
                 .writeln(&format!("-{:0.02}", sold.hammer_price()))?
                 .justify(JustifyMode::LEFT)?
-                .writeln("  (less club taking)")?
+                .writeln("  (less commission)")?
                 .justify(JustifyMode::RIGHT)?
-                .writeln(&format!("{:0.02}", sold.hammer_price() * club_taking))?
+                .writeln(&format!("{:0.02}", sold.commission()))?
                 .feed()?;
         }
     }
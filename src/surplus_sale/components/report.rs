@@ -0,0 +1,61 @@
+use dioxus::prelude::*;
+use dioxus_primitives::dialog::{DialogContent, DialogDescription, DialogRoot, DialogTitle};
+
+/// A report to show the user, such as the result of an integrity check
+/// or a merge
+#[derive(PartialEq, Clone, Default)]
+pub struct Report {
+    /// The title of the report
+    pub title: String,
+    /// A summary of the result
+    pub summary: String,
+    /// Each issue found, if any
+    pub lines: Vec<String>,
+}
+
+#[derive(PartialEq, Props, Clone)]
+pub struct ReportDialogProps {
+    open: Signal<bool>,
+    report: Report,
+}
+
+/// [`ReportDialog`] shows a [`Report`] to the user.
+#[component]
+pub fn ReportDialog(props: ReportDialogProps) -> Element {
+    let mut open = props.open;
+    let report = props.report;
+
+    rsx! {
+        DialogRoot {
+            class: "dialog-backdrop",
+            open: open(),
+            on_open_change: move |v| open.set(v),
+            DialogContent { class: "dialog",
+                button {
+                    class: "dialog-close",
+                    aria_label: "Close",
+                    tabindex: if open() { "0" } else { "-1" },
+                    onclick: move |_| open.set(false),
+                    "×"
+                }
+                DialogTitle { class: "dialog-title", "{report.title}" }
+                DialogDescription { class: "dialog-description", "{report.summary}" }
+
+                if !report.lines.is_empty() {
+                    ul {
+                        for line in &report.lines {
+                            li { key: "{line}", class: "select", "{line}" }
+                        }
+                    }
+                }
+
+                button {
+                    class: "button",
+                    "data-style": "primary",
+                    onclick: move |_| open.set(false),
+                    "Close"
+                }
+            }
+        }
+    }
+}
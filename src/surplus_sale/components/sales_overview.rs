@@ -1,15 +1,31 @@
 use bigdecimal::Zero;
 use dioxus::prelude::*;
+use dioxus_primitives::toast::{use_toast, ToastOptions};
 
-use crate::surplus_sale::types::Datafile;
+use crate::surplus_sale::{
+    components::AmendItem,
+    mutation::Mutation,
+    sync::Station,
+    types::{Datafile, Item},
+};
 
 #[component]
 pub fn SalesOverview() -> Element {
-    let mut datafile: Signal<Datafile> = use_context();
+    let toast_api = use_toast();
+    let datafile: Signal<Datafile> = use_context();
+    let station: Station = use_context();
     let sym = use_memo(move || datafile.read().currency().symbol());
 
-    let mut delete_item = move |lot_nmr| {
-        datafile.write().delete_item(lot_nmr);
+    let mut amend_open = use_signal(|| false);
+    let mut amending: Signal<Option<Item>> = use_signal(|| None);
+
+    let delete_item = move |lot_number| {
+        if let Err(e) = station.apply(Mutation::DeleteLot { lot_number }) {
+            toast_api.error(
+                "Failed to delete lot".to_string(),
+                ToastOptions::new().description(format!("{e}")),
+            );
+        }
     };
 
     rsx! {
@@ -79,6 +95,18 @@ pub fn SalesOverview() -> Element {
                             td {}
                         } else {
                             td {
+                                button {
+                                    class: "button",
+                                    "data-style": "secondary",
+                                    onclick: {
+                                        let item = item.clone();
+                                        move |_| {
+                                            amending.set(Some(item.clone()));
+                                            amend_open.set(true);
+                                        }
+                                    },
+                                    "Amend"
+                                }
                                 button {
                                     class: "button",
                                     "data-style": "destructive",
@@ -94,5 +122,9 @@ pub fn SalesOverview() -> Element {
                 }
             }
         }
+
+        if let Some(item) = amending() {
+            AmendItem { key: "{item}", open: amend_open, item }
+        }
     }
 }
@@ -1,8 +1,16 @@
+#[cfg(feature = "cli")]
+pub mod cli;
 mod components;
-mod export;
-mod types;
+mod mutation;
+#[cfg(feature = "phone-entry")]
+mod phone_entry;
+mod pin;
+mod recovery;
+mod sync;
 mod views;
 
+use tdars_core::surplus_sale::{commission, export, replay, types, validate};
+
 pub mod prelude {
     pub use super::views::SurplusSale;
 }
@@ -0,0 +1,228 @@
+use bigdecimal::{BigDecimal, Zero};
+use derive_more::Display;
+use iso_currency::Currency;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    surplus_sale::{
+        commission::CommissionRules,
+        types::{AuditItem, Datafile, Item, MutationError, ReconcileMethod, Tender},
+    },
+    types::Callsign,
+};
+
+/// A change made to a datafile by the user. Every change made while the
+/// file is open goes through a [`Mutation`], so that it can be shared
+/// with other stations and applied to their copy of the file in exactly
+/// the same way.
+///
+/// Each mutation displays as a short description of what it does, to
+/// name it in the undo history.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Display)]
+pub enum Mutation {
+    /// A lot went under the hammer, and was either sold or not
+    #[display("record lot {lot_number}")]
+    RecordLot {
+        lot_number: String,
+        seller: Callsign,
+        description: String,
+        sold: Option<(BigDecimal, Callsign)>,
+    },
+    /// See [`Datafile::delete_item`]
+    #[display("delete lot {lot_number}")]
+    DeleteLot { lot_number: String },
+    /// See [`Datafile::restore_item`]
+    #[display("restore lot {}", item.lot_number())]
+    RestoreLot { item: Box<Item> },
+    /// See [`Datafile::amend_item`]
+    #[display("amend lot {lot_number}")]
+    AmendLot {
+        lot_number: String,
+        description: String,
+        sold: Option<(BigDecimal, Callsign)>,
+    },
+    /// See [`Datafile::revert_item`]
+    #[display("revert lot {}", item.lot_number())]
+    RevertLot { item: Box<Item> },
+    /// See [`Datafile::reconcile`]
+    #[display("reconcile {}", callsign.callsign())]
+    Reconcile {
+        callsign: Callsign,
+        amount: BigDecimal,
+        method: ReconcileMethod,
+    },
+    /// See [`Datafile::settle`]
+    #[display("reconcile {}", callsign.callsign())]
+    Settle {
+        callsign: Callsign,
+        tenders: Vec<Tender>,
+    },
+    /// See [`Datafile::collect_debt`]
+    #[display("collect from {}", callsign.callsign())]
+    CollectDebt {
+        callsign: Callsign,
+        tenders: Vec<Tender>,
+    },
+    /// See [`Datafile::unreconcile`]
+    #[display("reverse a reconciliation")]
+    Unreconcile { audit_id: Uuid },
+    /// The currency and commission rules were changed
+    #[display("change the configuration")]
+    Configure {
+        currency: Currency,
+        commission_rules: CommissionRules,
+    },
+}
+
+impl Mutation {
+    /// Apply this mutation to a datafile, through the same methods
+    /// whichever station it was made at.
+    ///
+    /// Returns the change to be given back, which is zero for mutations
+    /// that don't take money.
+    ///
+    /// # Errors
+    ///
+    /// If the mutation is rejected, in which case the datafile is left
+    /// unchanged.
+    pub fn apply(&self, datafile: &mut Datafile) -> Result<BigDecimal, MutationError> {
+        match self {
+            Self::RecordLot {
+                lot_number,
+                seller,
+                description,
+                sold,
+            } => {
+                let mut item = Item::new(lot_number.clone(), seller.clone(), description.clone());
+                if let Some((hammer_price, buyer)) = sold {
+                    let commission = datafile.commission_for(seller, hammer_price);
+                    item.sold(hammer_price.clone(), buyer.clone(), commission);
+                }
+                datafile.push_item(item)?;
+            }
+            Self::DeleteLot { lot_number } => datafile.delete_item(lot_number.clone())?,
+            Self::RestoreLot { item } => datafile.restore_item((**item).clone())?,
+            Self::AmendLot {
+                lot_number,
+                description,
+                sold,
+            } => datafile.amend_item(lot_number, description.clone(), sold.clone())?,
+            Self::RevertLot { item } => datafile.revert_item((**item).clone())?,
+            Self::Reconcile {
+                callsign,
+                amount,
+                method,
+            } => return datafile.reconcile(callsign, amount.clone(), method.clone()),
+            Self::Settle { callsign, tenders } => return datafile.settle(callsign, tenders),
+            Self::CollectDebt { callsign, tenders } => {
+                return datafile.collect_debt(callsign, tenders);
+            }
+            Self::Unreconcile { audit_id } => datafile.unreconcile(*audit_id)?,
+            Self::Configure {
+                currency,
+                commission_rules,
+            } => {
+                datafile
+                    .set_currency(*currency)
+                    .set_commission_rules(commission_rules.clone());
+            }
+        }
+        Ok(BigDecimal::zero())
+    }
+
+    /// Apply this mutation to a datafile as [`Mutation::apply`] does,
+    /// also returning the mutation which would undo it, if it can be
+    /// undone.
+    ///
+    /// Undoing a mutation never removes anything from the audit log:
+    /// the inverse is an ordinary mutation, so it records its own entry
+    /// reversing the original, e.g. revoking a lot that was recorded.
+    ///
+    /// # Errors
+    ///
+    /// See [`Mutation::apply`].
+    pub fn apply_with_inverse(
+        &self,
+        datafile: &mut Datafile,
+    ) -> Result<(BigDecimal, Option<Self>), MutationError> {
+        let inverse = self.inverse(datafile);
+        let reconciliations = datafile.reconciliations().len();
+        let change = self.apply(datafile)?;
+        let inverse = match self {
+            // The reconciliation to reverse only exists once it is made
+            Self::Reconcile { .. } | Self::Settle { .. } | Self::CollectDebt { .. } => datafile
+                .reconciliations()
+                .get(reconciliations)
+                .map(|r| Self::Unreconcile {
+                    audit_id: *r.audit_id(),
+                }),
+            _ => inverse,
+        };
+        Ok((change, inverse))
+    }
+
+    /// The mutation which would undo this one, from the state of the
+    /// datafile before this one is applied.
+    fn inverse(&self, datafile: &Datafile) -> Option<Self> {
+        let item = |lot_number: &str| {
+            datafile
+                .items()
+                .iter()
+                .find(|i| i.lot_number() == lot_number)
+        };
+        match self {
+            Self::RecordLot { lot_number, .. } => {
+                // Deleting a lot with a number already in use would
+                // delete the wrong one
+                item(lot_number).is_none().then(|| Self::DeleteLot {
+                    lot_number: lot_number.clone(),
+                })
+            }
+            Self::DeleteLot { lot_number } => item(lot_number).map(|item| Self::RestoreLot {
+                item: Box::new(item.clone()),
+            }),
+            Self::RestoreLot { item } => Some(Self::DeleteLot {
+                lot_number: item.lot_number().clone(),
+            }),
+            // Amending recalculates the commission, so the lot is put back
+            // exactly as it was instead
+            Self::AmendLot { lot_number, .. } => item(lot_number).map(|item| Self::RevertLot {
+                item: Box::new(item.clone()),
+            }),
+            Self::RevertLot { item: reverted } => {
+                item(reverted.lot_number()).map(|item| Self::RevertLot {
+                    item: Box::new(item.clone()),
+                })
+            }
+            Self::Reconcile { .. } | Self::Settle { .. } | Self::CollectDebt { .. } => None,
+            Self::Unreconcile { audit_id } => {
+                let record = datafile
+                    .reconciliations()
+                    .iter()
+                    .find(|r| r.audit_id() == audit_id)?;
+                let callsign = record.callsign().clone();
+                let tenders = record.tenders().clone();
+                let collected = datafile.audit_log().iter().any(|e| {
+                    e.id() == audit_id && matches!(e.item(), AuditItem::DebtCollected { .. })
+                });
+                if collected {
+                    return Some(Self::CollectDebt { callsign, tenders });
+                }
+                // Settling again would also reconcile any lots of theirs
+                // which are unreconciled now, so can't redo the same one
+                let unreconciled = datafile.items().iter().any(|i| {
+                    i.sold_details().as_ref().is_some_and(|s| {
+                        (*i.seller_callsign() == callsign && s.seller_reconciled().is_none())
+                            || (*s.buyer_callsign() == callsign && s.buyer_reconciled().is_none())
+                    })
+                });
+                (!unreconciled).then_some(Self::Settle { callsign, tenders })
+            }
+            Self::Configure { .. } => Some(Self::Configure {
+                currency: *datafile.currency(),
+                commission_rules: datafile.commission_rules().clone(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{Ipv4Addr, UdpSocket},
+    str::FromStr,
+    time::Duration,
+};
+
+use bigdecimal::{BigDecimal, Zero};
+use dioxus::logger::tracing;
+use getset::Getters;
+use qrcode::{render::svg, QrCode};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    task::AbortHandle,
+};
+
+use crate::{surplus_sale::pin::Pin, types::Callsign};
+
+/// The TCP port phones connect to, unless another is given
+pub const DEFAULT_PORT: u16 = 7374;
+
+/// The largest request body accepted, which is far more than the form
+/// needs
+const MAX_REQUEST: usize = 16 * 1024;
+
+/// The largest request line and headers accepted
+const MAX_HEAD: usize = 8 * 1024;
+
+/// The longest a phone may take to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The lot entry form served to phones
+const FORM: &str = include_str!("../../assets/phone_entry.html");
+
+/// The fields of the lot entry form which are filled back in when a lot
+/// can't be recorded
+const FORM_FIELDS: [&str; 7] = [
+    "pin",
+    "seller",
+    "seller_name",
+    "description",
+    "buyer",
+    "buyer_name",
+    "hammer_price",
+];
+
+/// A lot entered on a phone, waiting to be recorded by the desktop app
+pub struct PhoneLot {
+    /// The seller, whose name is empty if it wasn't entered
+    pub seller: Callsign,
+    pub description: String,
+    /// The hammer price and buyer, if the lot sold
+    pub sold: Option<(BigDecimal, Callsign)>,
+    /// Sends the lot number it was recorded as, or why it wasn't, back
+    /// to the phone
+    reply: oneshot::Sender<Result<String, String>>,
+}
+
+impl PhoneLot {
+    /// Tell the phone the lot number this lot was recorded as, or why it
+    /// could not be recorded.
+    pub fn reply(self, result: Result<String, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// An embedded web server, serving a form for runners on the auction
+/// floor to enter lots from their phones. Dropping it stops the server.
+#[derive(Getters)]
+pub struct PhoneEntry {
+    /// The PIN phones must enter to record lots, which is new each time
+    /// the server starts
+    #[getset(get = "pub")]
+    pin: Pin,
+    /// The address of the form on this network
+    #[getset(get = "pub")]
+    url: String,
+    task: AbortHandle,
+}
+
+impl PhoneEntry {
+    /// Start serving the lot entry form on the port, to any address.
+    ///
+    /// Returns the server, and each lot as it is entered.
+    pub async fn start(port: u16) -> io::Result<(Self, mpsc::UnboundedReceiver<PhoneLot>)> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
+        let port = listener.local_addr()?.port();
+        let (lots, receiver) = mpsc::unbounded_channel();
+
+        let pin = Pin::generate();
+
+        let task = tokio::spawn({
+            let pin = pin.clone();
+            async move {
+                loop {
+                    let (stream, address) = match listener.accept().await {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!("Failed to accept phone: {e}");
+                            continue;
+                        }
+                    };
+                    let pin = pin.clone();
+                    let lots = lots.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, &pin, &lots).await {
+                            tracing::warn!("Failed to serve phone {address}: {e}");
+                        }
+                    });
+                }
+            }
+        })
+        .abort_handle();
+
+        Ok((
+            Self {
+                pin,
+                url: format!("http://{}:{port}/", local_address()),
+                task,
+            },
+            receiver,
+        ))
+    }
+
+    /// A QR code, as SVG, which opens the form with the PIN filled in
+    #[must_use]
+    pub fn qr_code(&self) -> String {
+        match QrCode::new(format!("{}?pin={}", self.url, self.pin)) {
+            Ok(code) => code.render::<svg::Color>().min_dimensions(200, 200).build(),
+            Err(e) => {
+                tracing::warn!("Failed to create QR code: {e}");
+                String::new()
+            }
+        }
+    }
+}
+
+impl Drop for PhoneEntry {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The address of this computer on the local network, as seen by other
+/// devices. Connecting a UDP socket sends nothing, but picks the
+/// interface that would be used.
+fn local_address() -> String {
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80))?;
+            socket.local_addr()
+        })
+        .map_or_else(|_| Ipv4Addr::LOCALHOST.to_string(), |a| a.ip().to_string())
+}
+
+/// An HTTP request from a phone
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    body: String,
+}
+
+/// Read an HTTP request, returning `None` if it is malformed or too
+/// large.
+async fn read_request(stream: &mut TcpStream) -> io::Result<Option<Request>> {
+    let mut data = vec![];
+    let mut buffer = [0; 4096];
+    let head_end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if data.len() > MAX_HEAD {
+            return Ok(None);
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        data.extend_from_slice(&buffer[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_REQUEST {
+        return Ok(None);
+    }
+
+    let mut body = data[head_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }))
+}
+
+/// Answer one request from a phone.
+async fn serve(
+    mut stream: TcpStream,
+    pin: &Pin,
+    lots: &mpsc::UnboundedSender<PhoneLot>,
+) -> io::Result<()> {
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await;
+    let response = match request {
+        Err(_) => respond("408 Request Timeout", "text/plain", "Request timeout"),
+        Ok(request) => match request? {
+            None => respond("400 Bad Request", "text/plain", "Bad request"),
+            Some(request) => answer(request, pin, lots).await,
+        },
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Answer a request for the form, or to record a lot.
+async fn answer(request: Request, pin: &Pin, lots: &mpsc::UnboundedSender<PhoneLot>) -> String {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => {
+            let mut form = parse_form(&request.query);
+            form.retain(|name, _| name == "pin");
+            respond("200 OK", "text/html", &page(&form, None))
+        }
+        ("POST", "/lot") => {
+            let form = parse_form(&request.body);
+            respond("200 OK", "text/html", &record(form, pin, lots).await)
+        }
+        _ => respond("404 Not Found", "text/plain", "Not found"),
+    }
+}
+
+/// Record a lot entered on a phone, returning the page to show next.
+async fn record(
+    mut form: HashMap<String, String>,
+    pin: &Pin,
+    lots: &mpsc::UnboundedSender<PhoneLot>,
+) -> String {
+    let field = |name: &str| form.get(name).map_or("", |v| v.trim()).to_string();
+
+    if let Err(e) = pin.check(&field("pin")).await {
+        return page(&form, Some(Err(e.to_string())));
+    }
+
+    let callsign = |callsign: String, name: String| {
+        Callsign::default()
+            .with_callsign(callsign.to_uppercase())
+            .with_name(name)
+    };
+    let seller = callsign(field("seller"), field("seller_name"));
+    let description = field("description");
+    if seller.callsign().is_empty() || description.is_empty() {
+        return page(
+            &form,
+            Some(Err("Enter the seller and a description".to_string())),
+        );
+    }
+    let sold = if form.contains_key("sold") {
+        let buyer = callsign(field("buyer"), field("buyer_name"));
+        let hammer_price = BigDecimal::from_str(&field("hammer_price"))
+            .ok()
+            .filter(|p| *p >= BigDecimal::zero());
+        match hammer_price {
+            Some(hammer_price) if !buyer.callsign().is_empty() => Some((hammer_price, buyer)),
+            _ => {
+                return page(
+                    &form,
+                    Some(Err(
+                        "Enter the buyer and hammer price of a sold lot".to_string()
+                    )),
+                );
+            }
+        }
+    } else {
+        None
+    };
+
+    let (reply, result) = oneshot::channel();
+    let sent = lots.send(PhoneLot {
+        seller,
+        description,
+        sold,
+        reply,
+    });
+    let result = match (sent, result.await) {
+        (Ok(()), Ok(result)) => result,
+        _ => Err("The desktop app has stopped accepting lots".to_string()),
+    };
+    if result.is_ok() {
+        // Keep the PIN, ready for the next lot
+        form.retain(|name, _| name == "pin");
+    }
+    page(
+        &form,
+        Some(result.map(|lot_number| format!("Recorded lot {lot_number}"))),
+    )
+}
+
+/// Render the lot entry form, filled in with the values given, and with
+/// a message showing whether the last lot was recorded.
+fn page(form: &HashMap<String, String>, message: Option<Result<String, String>>) -> String {
+    let message = match message {
+        None => String::new(),
+        Some(Ok(message)) => format!(r#"<p class="message">{}</p>"#, escape(&message)),
+        Some(Err(message)) => format!(r#"<p class="message error">{}</p>"#, escape(&message)),
+    };
+    // A new form has the sold box ticked
+    let new_form = form.keys().all(|name| name == "pin");
+    let sold = if new_form || form.contains_key("sold") {
+        "checked"
+    } else {
+        ""
+    };
+
+    let mut page = FORM.replace("{message}", &message).replace("{sold}", sold);
+    for name in FORM_FIELDS {
+        let value = form.get(name).map(|v| escape(v)).unwrap_or_default();
+        page = page.replace(&format!("{{{name}}}"), &value);
+    }
+    page
+}
+
+/// Build an HTTP response.
+fn respond(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\nCache-Control: no-store\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Parse an `application/x-www-form-urlencoded` form or query string.
+fn parse_form(data: &str) -> HashMap<String, String> {
+    data.split('&')
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, value)| (decode(name), decode(value)))
+        .collect()
+}
+
+/// Decode a percent-encoded form value.
+fn decode(value: &str) -> String {
+    let mut bytes = vec![];
+    let mut input = value.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next(), input.next()];
+                let decoded = match hex {
+                    [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                        .ok()
+                        .and_then(|h| u8::from_str_radix(h, 16).ok()),
+                    _ => None,
+                };
+                bytes.push(decoded.unwrap_or(b'?'));
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Escape text to be shown in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+        // So that values can't be mistaken for placeholders in the form
+        .replace('{', "&#123;")
+}
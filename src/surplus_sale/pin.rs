@@ -0,0 +1,68 @@
+use std::{sync::Arc, time::Duration};
+
+use derive_more::Display;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// How long to wait before answering a wrong PIN, to slow down guessing
+const WRONG_PIN_DELAY: Duration = Duration::from_secs(1);
+
+/// The number of wrong PINs given before every later attempt is refused
+const MAX_WRONG_PINS: usize = 10;
+
+/// Why a PIN was refused
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum PinError {
+    #[display("The PIN is wrong")]
+    Wrong,
+    #[display("Too many wrong PINs have been given, so no more are accepted")]
+    LockedOut,
+}
+
+/// A six digit PIN which other devices must give to use this station,
+/// which is new each time one is generated.
+///
+/// PINs are checked one at a time, so guesses can't be made in parallel,
+/// and once too many wrong PINs have been given no more are accepted.
+#[derive(Clone, Display)]
+#[display("{pin}")]
+pub struct Pin {
+    pin: Arc<str>,
+    /// The number of wrong PINs given so far
+    failures: Arc<Mutex<usize>>,
+}
+
+impl Pin {
+    /// Generate a new PIN
+    #[must_use]
+    pub fn generate() -> Self {
+        let random = Uuid::new_v4().into_bytes();
+        let pin = format!(
+            "{:06}",
+            u32::from_le_bytes([random[0], random[1], random[2], random[3]]) % 1_000_000
+        );
+        Self {
+            pin: pin.into(),
+            failures: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Check a PIN given by another device. Wrong PINs are answered after
+    /// a delay, while every other check waits.
+    ///
+    /// # Errors
+    ///
+    /// If the PIN is wrong, or too many wrong PINs have been given.
+    pub async fn check(&self, given: &str) -> Result<(), PinError> {
+        let mut failures = self.failures.lock().await;
+        if *failures >= MAX_WRONG_PINS {
+            return Err(PinError::LockedOut);
+        }
+        if given.trim() == &*self.pin {
+            return Ok(());
+        }
+        *failures += 1;
+        tokio::time::sleep(WRONG_PIN_DELAY).await;
+        Err(PinError::Wrong)
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use dioxus::logger::tracing;
+
+use crate::{
+    encryption::{self, Key},
+    surplus_sale::types::Datafile,
+};
+
+/// The number of snapshots kept in the recovery journal
+const KEEP_SNAPSHOTS: usize = 5;
+
+/// The extension of each snapshot in the recovery journal
+const SNAPSHOT_EXTENSION: &str = "tdars_auction";
+
+/// The directory holding the recovery journal, in the OS data directory.
+fn journal_dir() -> io::Result<PathBuf> {
+    dirs::data_dir()
+        .map(|d| d.join("tdars-toolkit").join("surplus-sale-recovery"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))
+}
+
+/// Return the snapshots in the journal, oldest first. Snapshots are named
+/// by the time they were taken, so sort in order.
+fn snapshots(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut snapshots = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == SNAPSHOT_EXTENSION))
+        .collect::<Vec<_>>();
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Record a snapshot of the datafile in the recovery journal, dropping
+/// the oldest snapshots so that only the most recent few are kept.
+///
+/// Each snapshot is written to a temporary file first, so that a crash
+/// part way through writing never leaves a partial snapshot. Snapshots of
+/// an encrypted file are encrypted with its key.
+pub fn record(datafile: &Datafile, key: Option<&Key>) -> io::Result<()> {
+    let dir = journal_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    let mut data = serde_json::to_vec(datafile)?;
+    if let Some(key) = key {
+        data = key.seal(&data);
+    }
+    let name = format!(
+        "{}.{SNAPSHOT_EXTENSION}",
+        Local::now().format("%Y%m%d%H%M%S%f")
+    );
+    let temp = dir.join(format!("{name}.tmp"));
+    fs::write(&temp, data)?;
+    fs::rename(&temp, dir.join(name))?;
+
+    let snapshots = snapshots(&dir)?;
+    let excess = snapshots.len().saturating_sub(KEEP_SNAPSHOTS);
+    for old in &snapshots[..excess] {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Return the most recent snapshot in the recovery journal that can be
+/// loaded, with the time it was taken. Encrypted snapshots can't be
+/// checked until they are decrypted, so are returned as they are.
+#[must_use]
+pub fn latest() -> Option<(DateTime<Local>, Vec<u8>)> {
+    let dir = journal_dir().ok()?;
+    snapshots(&dir).ok()?.into_iter().rev().find_map(|path| {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        let data = fs::read(&path).ok()?;
+        if encryption::is_encrypted(&data) {
+            return Some((modified.into(), data));
+        }
+        match Datafile::load(&data) {
+            Ok(_) => Some((modified.into(), data)),
+            Err(e) => {
+                tracing::warn!("Skipping unreadable recovery snapshot {path:?}: {e}");
+                None
+            }
+        }
+    })
+}
+
+/// Clear the recovery journal, once the file it protects has been closed
+/// cleanly.
+pub fn clear() -> io::Result<()> {
+    let dir = journal_dir()?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    for snapshot in snapshots(&dir)? {
+        fs::remove_file(snapshot)?;
+    }
+    Ok(())
+}
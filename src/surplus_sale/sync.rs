@@ -0,0 +1,677 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bigdecimal::BigDecimal;
+use derive_more::Display;
+use dioxus::{logger::tracing, prelude::*};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    sync::{broadcast, mpsc},
+    task::AbortHandle,
+};
+
+use crate::{
+    history::{History, HistoryError, Step},
+    surplus_sale::{
+        mutation::Mutation,
+        pin::Pin,
+        types::{Datafile, MutationError},
+        NeedsSaving,
+    },
+};
+
+/// The TCP port stations sync over, unless another is given
+pub const DEFAULT_PORT: u16 = 7373;
+
+/// The longest a station joining may take to give the host's PIN, and
+/// the host to answer it
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest message the host accepts from a client, which is far
+/// more than any mutation needs
+const MAX_CLIENT_MESSAGE: usize = 1024 * 1024;
+
+/// The largest snapshot a client accepts from the host
+const MAX_SNAPSHOT: usize = 64 * 1024 * 1024;
+
+/// A message sent between stations, as one line of JSON.
+///
+/// A client first sends the PIN shown by the host, which answers with its
+/// datafile, or why the client was refused. Clients then send each
+/// mutation made at their station to the host, which applies it to its
+/// datafile. After every change, the host sends the whole datafile to
+/// every client, so that every station shows exactly the host's copy.
+#[derive(Serialize, Deserialize)]
+enum Message {
+    /// From a client joining: the host's PIN
+    Join { pin: String },
+    /// From the host: why a client joining was refused
+    Refused(String),
+    /// From the host: its datafile
+    Snapshot(Box<Datafile>),
+    /// From a client: a mutation made at that station
+    Mutate(Mutation),
+}
+
+impl Message {
+    /// Encode the message as a line to send.
+    fn encode(&self) -> Arc<str> {
+        #[allow(
+            clippy::unwrap_used,
+            reason = "the format is guaranteed to be serializable"
+        )]
+        let mut line = serde_json::to_string(self).unwrap();
+        line.push('\n');
+        line.into()
+    }
+}
+
+/// Whether this station is syncing with others
+#[derive(Clone, PartialEq, Display)]
+pub enum SyncStatus {
+    #[display("Not syncing")]
+    Offline,
+    #[display("Hosting on port {port} with PIN {pin}, {clients} station(s) connected")]
+    Hosting {
+        port: u16,
+        pin: String,
+        clients: usize,
+    },
+    #[display("Connected to {host}")]
+    Connected { host: SocketAddr },
+    #[display("Disconnected: {reason}")]
+    Disconnected { reason: String },
+}
+
+/// Something that happened on the network, to be handled by the UI
+enum Event {
+    /// A client made a mutation
+    Mutation(Mutation),
+    /// The host's datafile changed
+    Snapshot(Box<Datafile>),
+    /// The sync status changed
+    Status(SyncStatus),
+}
+
+/// How this station takes part in syncing
+enum Role {
+    Host {
+        /// Sends each snapshot to every client
+        snapshots: broadcast::Sender<Arc<str>>,
+        /// The latest snapshot, for clients which have just connected or
+        /// fallen behind
+        latest: Arc<Mutex<Arc<str>>>,
+    },
+    Client {
+        /// Sends each mutation to the host
+        mutations: mpsc::UnboundedSender<Mutation>,
+    },
+}
+
+/// A connection to the other stations. Dropping it disconnects.
+struct Connection {
+    role: Role,
+    /// The network tasks, to stop when disconnecting
+    tasks: Arc<Mutex<Vec<AbortHandle>>>,
+}
+
+impl Connection {
+    /// Start hosting the datafile on the port, accepting clients from any
+    /// address which give the PIN shown in the status.
+    async fn host(
+        port: u16,
+        datafile: &Datafile,
+    ) -> io::Result<(Self, mpsc::UnboundedReceiver<Event>)> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
+        let port = listener.local_addr()?.port();
+        let pin = Pin::generate();
+        let (events, receiver) = mpsc::unbounded_channel();
+        let (snapshots, _) = broadcast::channel(16);
+        let latest = Arc::new(Mutex::new(
+            Message::Snapshot(Box::new(datafile.clone())).encode(),
+        ));
+        let tasks: Arc<Mutex<Vec<AbortHandle>>> = Arc::new(Mutex::new(vec![]));
+        let clients = Arc::new(AtomicUsize::new(0));
+        let shown_pin = pin.to_string();
+        let hosting = move |connected| {
+            Event::Status(SyncStatus::Hosting {
+                port,
+                pin: shown_pin.clone(),
+                clients: connected,
+            })
+        };
+        let _ = events.send(hosting(0));
+
+        let accept = tokio::spawn({
+            let snapshots = snapshots.clone();
+            let latest = latest.clone();
+            let tasks = tasks.clone();
+            async move {
+                loop {
+                    let (stream, address) = match listener.accept().await {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!("Failed to accept station: {e}");
+                            continue;
+                        }
+                    };
+                    let (reader, mut writer) = stream.into_split();
+                    let mut reader = BufReader::new(reader);
+                    let joining = tokio::spawn({
+                        let pin = pin.clone();
+                        let hosting = hosting.clone();
+                        let events = events.clone();
+                        let clients = clients.clone();
+                        let snapshots = snapshots.clone();
+                        let latest = latest.clone();
+                        let tasks = tasks.clone();
+                        async move {
+                            if let Err(reason) = admit(&mut reader, &pin).await {
+                                tracing::warn!("Refused station {address}: {reason}");
+                                let refused = Message::Refused(reason).encode();
+                                let _ = writer.write_all(refused.as_bytes()).await;
+                                return;
+                            }
+                            tracing::info!("Station {address} connected");
+                            let connected = clients.fetch_add(1, Ordering::SeqCst) + 1;
+                            let _ = events.send(hosting(connected));
+
+                            let sending =
+                                tokio::spawn(send_snapshots(writer, snapshots.subscribe(), latest));
+                            tasks.lock().push(sending.abort_handle());
+                            if let Err(e) = receive(reader, &events, false).await {
+                                tracing::warn!("Station {address} disconnected: {e}");
+                            } else {
+                                tracing::info!("Station {address} disconnected");
+                            }
+                            sending.abort();
+                            let connected = clients.fetch_sub(1, Ordering::SeqCst) - 1;
+                            let _ = events.send(hosting(connected));
+                        }
+                    });
+                    tasks.lock().push(joining.abort_handle());
+                }
+            }
+        });
+        tasks.lock().push(accept.abort_handle());
+
+        Ok((
+            Self {
+                role: Role::Host { snapshots, latest },
+                tasks,
+            },
+            receiver,
+        ))
+    }
+
+    /// Join the host at the address, which is given as `host:port`, or
+    /// just `host` to use [`DEFAULT_PORT`], with the PIN it shows.
+    async fn join(address: &str, pin: &str) -> io::Result<(Self, mpsc::UnboundedReceiver<Event>)> {
+        let stream = if address.contains(':') {
+            TcpStream::connect(address).await?
+        } else {
+            TcpStream::connect((address, DEFAULT_PORT)).await?
+        };
+        let host = stream.peer_addr()?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let join = Message::Join {
+            pin: pin.to_string(),
+        };
+        writer.write_all(join.encode().as_bytes()).await?;
+        let answer = tokio::time::timeout(JOIN_TIMEOUT, read_message(&mut reader, MAX_SNAPSHOT))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "the host did not answer"))??;
+        let snapshot = match answer {
+            Some(Message::Snapshot(datafile)) => datafile,
+            Some(Message::Refused(reason)) => {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, reason));
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the host did not send its file",
+                ));
+            }
+        };
+
+        let (events, receiver) = mpsc::unbounded_channel();
+        let (mutations, outgoing) = mpsc::unbounded_channel();
+        let _ = events.send(Event::Status(SyncStatus::Connected { host }));
+        let _ = events.send(Event::Snapshot(snapshot));
+
+        let sending = tokio::spawn(send_mutations(writer, outgoing));
+        let receiving = tokio::spawn(async move {
+            let reason = match receive(reader, &events, true).await {
+                Ok(()) => "the host stopped syncing".to_string(),
+                Err(e) => e.to_string(),
+            };
+            tracing::warn!("Disconnected from {host}: {reason}");
+            let _ = events.send(Event::Status(SyncStatus::Disconnected { reason }));
+        });
+
+        Ok((
+            Self {
+                role: Role::Client { mutations },
+                tasks: Arc::new(Mutex::new(vec![
+                    sending.abort_handle(),
+                    receiving.abort_handle(),
+                ])),
+            },
+            receiver,
+        ))
+    }
+
+    /// If hosting, send the datafile to every client.
+    fn publish(&self, datafile: &Datafile) {
+        if let Role::Host { snapshots, latest } = &self.role {
+            let snapshot = Message::Snapshot(Box::new(datafile.clone())).encode();
+            *latest.lock() = snapshot.clone();
+            // This only fails if no clients are connected
+            let _ = snapshots.send(snapshot);
+        }
+    }
+
+    /// If a client, send a mutation made at this station to the host.
+    fn submit(&self, mutation: Mutation) {
+        if let Role::Client { mutations } = &self.role {
+            let _ = mutations.send(mutation);
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// Send the latest snapshot to a client, then every snapshot after it.
+async fn send_snapshots(
+    mut writer: OwnedWriteHalf,
+    mut snapshots: broadcast::Receiver<Arc<str>>,
+    latest: Arc<Mutex<Arc<str>>>,
+) -> io::Result<()> {
+    let snapshot = latest.lock().clone();
+    writer.write_all(snapshot.as_bytes()).await?;
+    loop {
+        let snapshot = match snapshots.recv().await {
+            Ok(snapshot) => snapshot,
+            // Each snapshot is the whole datafile, so a client which has
+            // fallen behind only needs the latest
+            Err(broadcast::error::RecvError::Lagged(_)) => latest.lock().clone(),
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        writer.write_all(snapshot.as_bytes()).await?;
+    }
+}
+
+/// Send each mutation made at this station to the host.
+async fn send_mutations(
+    mut writer: OwnedWriteHalf,
+    mut mutations: mpsc::UnboundedReceiver<Mutation>,
+) -> io::Result<()> {
+    while let Some(mutation) = mutations.recv().await {
+        writer
+            .write_all(Message::Mutate(mutation).encode().as_bytes())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Wait for a station joining to give the PIN, returning why it was
+/// refused if it doesn't.
+async fn admit(reader: &mut BufReader<OwnedReadHalf>, pin: &Pin) -> Result<(), String> {
+    let message = tokio::time::timeout(JOIN_TIMEOUT, read_message(reader, MAX_CLIENT_MESSAGE))
+        .await
+        .map_err(|_| "the PIN was not given in time".to_string())?
+        .map_err(|e| e.to_string())?;
+    match message {
+        Some(Message::Join { pin: given }) => pin.check(&given).await.map_err(|e| e.to_string()),
+        _ => Err("the PIN was not given".to_string()),
+    }
+}
+
+/// Read a message from another station, refusing any longer than `max`
+/// bytes. Returns `None` once the station disconnects.
+async fn read_message(
+    reader: &mut BufReader<OwnedReadHalf>,
+    max: usize,
+) -> io::Result<Option<Message>> {
+    let mut line = vec![];
+    let limit = u64::try_from(max).unwrap_or(u64::MAX).saturating_add(1);
+    (&mut *reader)
+        .take(limit)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if line.last() != Some(&b'\n') {
+        if line.len() > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the message is too large",
+            ));
+        }
+        // Disconnected, possibly part way through a message
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&line)?))
+}
+
+/// Receive messages from another station until it disconnects. Only the
+/// host may send snapshots, and only clients may send mutations.
+async fn receive(
+    mut reader: BufReader<OwnedReadHalf>,
+    events: &mpsc::UnboundedSender<Event>,
+    from_host: bool,
+) -> io::Result<()> {
+    let max = if from_host {
+        MAX_SNAPSHOT
+    } else {
+        MAX_CLIENT_MESSAGE
+    };
+    while let Some(message) = read_message(&mut reader, max).await? {
+        let event = match message {
+            Message::Snapshot(datafile) if from_host => Event::Snapshot(datafile),
+            Message::Mutate(mutation) if !from_host => Event::Mutation(mutation),
+            _ => {
+                tracing::warn!("Ignoring message sent by the wrong station");
+                continue;
+            }
+        };
+        if events.send(event).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// This station, which every change to the open datafile is made through
+/// so that it can be shared with the other stations.
+#[derive(Clone, Copy)]
+pub struct Station {
+    datafile: Signal<Datafile>,
+    needs_saving: Signal<NeedsSaving>,
+    connection: Signal<Option<Connection>>,
+    status: Signal<SyncStatus>,
+    /// The changes made at this station which can be undone and redone
+    history: Signal<History<Mutation>>,
+}
+
+impl Station {
+    pub fn new(datafile: Signal<Datafile>, needs_saving: Signal<NeedsSaving>) -> Self {
+        Self {
+            datafile,
+            needs_saving,
+            connection: Signal::new(None),
+            status: Signal::new(SyncStatus::Offline),
+            history: Signal::new(History::default()),
+        }
+    }
+
+    /// The datafile open at this station
+    pub fn datafile(self) -> Signal<Datafile> {
+        self.datafile
+    }
+
+    /// Whether this station is syncing with others
+    pub fn status(self) -> Signal<SyncStatus> {
+        self.status
+    }
+
+    /// Whether this station is connected to others
+    pub fn is_connected(self) -> bool {
+        self.connection.read().is_some()
+    }
+
+    /// Apply a mutation to the datafile, then share it with the host if
+    /// this station is a client. See [`Mutation::apply`].
+    ///
+    /// The mutation can then be undone, unless it can't be reversed, in
+    /// which case nothing made before it can be undone either.
+    ///
+    /// # Errors
+    ///
+    /// If the mutation is rejected, in which case nothing is changed or
+    /// shared.
+    pub fn apply(mut self, mutation: Mutation) -> Result<BigDecimal, MutationError> {
+        let (change, inverse) = self.commit(&mutation)?;
+        let mut history = self.history.write();
+        match inverse {
+            Some(inverse) => history.record(Step::new(mutation.to_string(), inverse)),
+            None => history.clear(),
+        }
+        Ok(change)
+    }
+
+    /// Undo the last change made at this station, by applying a mutation
+    /// which reverses it. Returns the description of the change undone.
+    ///
+    /// If the file has changed since so that the change can no longer be
+    /// reversed, e.g. because the lot has since been reconciled, nothing
+    /// made before it can be undone either.
+    pub fn undo(mut self) -> Result<String, HistoryError> {
+        let step = self.history.write().take_undo()?;
+        let redo = match self.commit(step.inverse()) {
+            Ok((_, redo)) => redo,
+            Err(e) => {
+                tracing::warn!("Could not undo {}: {e}", step.description());
+                self.history.write().clear();
+                return Err(HistoryError::Rejected(step.description().clone()));
+            }
+        };
+        let mut history = self.history.write();
+        match redo {
+            Some(redo) => history.undone(Step::new(step.description().clone(), redo)),
+            None => history.clear(),
+        }
+        Ok(step.description().clone())
+    }
+
+    /// Redo the last change undone at this station. Returns the
+    /// description of the change redone.
+    pub fn redo(mut self) -> Result<String, HistoryError> {
+        let step = self.history.write().take_redo()?;
+        let undo = match self.commit(step.inverse()) {
+            Ok((_, undo)) => undo,
+            Err(e) => {
+                tracing::warn!("Could not redo {}: {e}", step.description());
+                self.history.write().clear();
+                return Err(HistoryError::Rejected(step.description().clone()));
+            }
+        };
+        let mut history = self.history.write();
+        match undo {
+            Some(undo) => history.redone(Step::new(step.description().clone(), undo)),
+            None => history.clear(),
+        }
+        Ok(step.description().clone())
+    }
+
+    /// The description of the change [`Station::undo`] would undo
+    pub fn next_undo(self) -> Option<String> {
+        self.history
+            .read()
+            .next_undo()
+            .map(|s| s.description().clone())
+    }
+
+    /// The description of the change [`Station::redo`] would redo
+    pub fn next_redo(self) -> Option<String> {
+        self.history
+            .read()
+            .next_redo()
+            .map(|s| s.description().clone())
+    }
+
+    /// Forget every change that could be undone or redone. This should be
+    /// called whenever a different datafile is opened.
+    pub fn clear_history(mut self) {
+        self.history.write().clear();
+    }
+
+    /// Apply a mutation made at this station, marking the datafile as
+    /// needing saving and sharing it with the host if this station is a
+    /// client. Returns the change to be given back and the mutation which
+    /// would undo it, see [`Mutation::apply_with_inverse`].
+    fn commit(
+        mut self,
+        mutation: &Mutation,
+    ) -> Result<(BigDecimal, Option<Mutation>), MutationError> {
+        let result = mutation.apply_with_inverse(&mut self.datafile.write())?;
+        self.needs_saving.set(NeedsSaving(true));
+        if let Some(connection) = self.connection.peek().as_ref() {
+            connection.submit(mutation.clone());
+        }
+        Ok(result)
+    }
+
+    /// If hosting, send the datafile to every client. This should be
+    /// called after every change to it.
+    pub fn publish(self, datafile: &Datafile) {
+        if let Some(connection) = self.connection.peek().as_ref() {
+            connection.publish(datafile);
+        }
+    }
+
+    /// Start hosting the open datafile for other stations to join.
+    pub async fn host(self, port: u16) -> io::Result<()> {
+        let datafile = self.datafile.peek().clone();
+        let (connection, events) = Connection::host(port, &datafile).await?;
+        self.connect(connection, events);
+        Ok(())
+    }
+
+    /// Join a host with the PIN it shows, replacing the open datafile with
+    /// the host's.
+    pub async fn join(self, address: &str, pin: &str) -> io::Result<()> {
+        let (connection, events) = Connection::join(address, pin).await?;
+        self.connect(connection, events);
+        Ok(())
+    }
+
+    /// Stop syncing with other stations.
+    pub fn disconnect(mut self) {
+        self.connection.set(None);
+        self.status.set(SyncStatus::Offline);
+    }
+
+    /// Handle events from the connection until it is replaced or closed.
+    fn connect(mut self, connection: Connection, mut events: mpsc::UnboundedReceiver<Event>) {
+        self.connection.set(Some(connection));
+        spawn(async move {
+            while let Some(event) = events.recv().await {
+                match event {
+                    Event::Mutation(mutation) => {
+                        if let Err(e) = mutation.apply(&mut self.datafile.write()) {
+                            tracing::warn!("Rejected mutation from another station: {e}");
+                        }
+                        self.needs_saving.set(NeedsSaving(true));
+                    }
+                    Event::Snapshot(datafile) => {
+                        self.datafile.set(*datafile);
+                        self.needs_saving.set(NeedsSaving(true));
+                    }
+                    Event::Status(status) => {
+                        if matches!(status, SyncStatus::Disconnected { .. }) {
+                            self.connection.set(None);
+                        }
+                        self.status.set(status);
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used, reason = "tests fail by panicking")]
+mod tests {
+    use super::*;
+    use crate::types::Callsign;
+
+    /// Wait for the next event, which over this machine should be quick
+    async fn next(events: &mut mpsc::UnboundedReceiver<Event>) -> Event {
+        tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("an event arrives in time")
+            .expect("the connection is open")
+    }
+
+    /// A lot sold by the seller, numbered after them
+    fn lot(seller: &str) -> Mutation {
+        let seller = Callsign::default().with_callsign(seller.to_string());
+        let buyer = Callsign::default().with_callsign("M0BUY".to_string());
+        Mutation::RecordLot {
+            lot_number: format!("{}-1", seller.callsign()),
+            seller,
+            description: "Lot".to_string(),
+            sold: Some((BigDecimal::from(10), buyer)),
+        }
+    }
+
+    #[tokio::test]
+    async fn host_and_client_converge() {
+        let mut hosted = Datafile::new();
+        let (host, mut host_events) = Connection::host(0, &hosted).await.expect("hosting");
+        let Event::Status(SyncStatus::Hosting { port, pin, .. }) = next(&mut host_events).await
+        else {
+            panic!("the host reports hosting first");
+        };
+        let address = format!("127.0.0.1:{port}");
+
+        assert!(Connection::join(&address, "wrong").await.is_err());
+
+        let (client, mut client_events) = Connection::join(&address, &pin)
+            .await
+            .expect("joining with the PIN");
+        let mut joined = Datafile::new();
+
+        // Each station records a lot, the client sharing it with the host
+        let from_host = lot("M0HST");
+        from_host.apply(&mut hosted).expect("the lot is new");
+        host.publish(&hosted);
+        let from_client = lot("M0CLI");
+        from_client.apply(&mut joined).expect("the lot is new");
+        client.submit(from_client);
+
+        // The host applies the client's lot and shares the result
+        loop {
+            match next(&mut host_events).await {
+                Event::Mutation(mutation) => {
+                    mutation.apply(&mut hosted).expect("the lot is new");
+                    host.publish(&hosted);
+                    break;
+                }
+                Event::Status(_) => (),
+                Event::Snapshot(_) => panic!("the host was sent a snapshot"),
+            }
+        }
+
+        // The client takes each snapshot until it has both lots
+        while joined.items().len() < 2 {
+            match next(&mut client_events).await {
+                Event::Snapshot(datafile) => joined = *datafile,
+                Event::Status(status) => {
+                    assert!(!matches!(status, SyncStatus::Disconnected { .. }));
+                }
+                Event::Mutation(_) => panic!("the client was sent a mutation"),
+            }
+        }
+        assert!(joined == hosted);
+    }
+}
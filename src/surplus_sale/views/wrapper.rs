@@ -1,14 +1,27 @@
-use std::io::Cursor;
 use std::time::Duration;
 
 use chrono::Local;
+use derive_more::Display;
 use dioxus::{logger::tracing, prelude::*};
 use dioxus_primitives::navbar::{Navbar, NavbarContent, NavbarItem, NavbarNav, NavbarTrigger};
 use dioxus_primitives::toast::{use_toast, ToastOptions};
 
-use crate::surplus_sale::components::LoadedFile;
+use crate::components::{
+    use_history_shortcuts, use_passphrase_prompt, HistoryShortcut, PassphraseDialog,
+    PassphrasePrompt,
+};
+use crate::encryption::{self, DecryptError, Key};
+use crate::history::HistoryError;
+use crate::schema::LoadError;
+use crate::surplus_sale::components::{
+    JoinStation, LoadedFile, PhoneEntryDialog, Report, ReportDialog,
+};
 use crate::surplus_sale::export::export;
+use crate::surplus_sale::recovery;
+use crate::surplus_sale::replay::check_integrity;
+use crate::surplus_sale::sync::{Station, DEFAULT_PORT};
 use crate::surplus_sale::types::Datafile;
+use crate::surplus_sale::validate::validate;
 use crate::surplus_sale::NeedsSaving;
 use crate::Route;
 
@@ -23,6 +36,134 @@ pub fn SurplusSale() -> Element {
     let mut datafile: Signal<Datafile> = use_signal(Datafile::new);
     let mut datafile_open = use_signal(|| false);
     let mut configure_open = use_signal(|| false);
+    let mut report_open = use_signal(|| false);
+    let mut report = use_signal(Report::default);
+    let mut file_handle: Signal<Option<rfd::FileHandle>> = use_signal(|| None);
+    // The key the open file is encrypted with, if it is encrypted
+    let mut key: Signal<Option<Key>> = use_signal(|| None);
+    let prompt = use_passphrase_prompt();
+    let station = use_context_provider(|| Station::new(datafile, needs_saving));
+    let sync_status = station.status();
+    let mut join_open = use_signal(|| false);
+    let mut phone_entry_open = use_signal(|| false);
+
+    let move_through_history = move |shortcut: HistoryShortcut| {
+        if !*datafile_open.peek() {
+            return;
+        }
+        let (result, done, failed) = match shortcut {
+            HistoryShortcut::Undo => (station.undo(), "Undid", "Failed to undo"),
+            HistoryShortcut::Redo => (station.redo(), "Redid", "Failed to redo"),
+        };
+        match result {
+            Ok(description) => {
+                toast_api.info(
+                    format!("{done} {description}"),
+                    ToastOptions::new().permanent(false).duration(INFO_DURATION),
+                );
+            }
+            Err(HistoryError::NothingToUndo | HistoryError::NothingToRedo) => (),
+            Err(e) => {
+                toast_api.error(
+                    failed.to_string(),
+                    ToastOptions::new()
+                        .description(format!("{e}"))
+                        .permanent(false)
+                        .duration(ERROR_DURATION),
+                );
+            }
+        }
+    };
+    use_history_shortcuts(move_through_history);
+
+    use_effect(move || {
+        // Offer to restore from the recovery journal if the last file
+        // wasn't closed cleanly
+        spawn(async move {
+            let Some((moment, recovered)) = recovery::latest() else {
+                return;
+            };
+            let moment = moment.format("%F %T");
+            let response = rfd::AsyncMessageDialog::new()
+                .set_title("Recover unsaved work?")
+                .set_description(format!(
+                    "A surplus sale file was not closed cleanly. Do you want to restore it as it was at {moment}?",
+                ))
+                .set_level(rfd::MessageLevel::Warning)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                .await;
+            if response == rfd::MessageDialogResult::Yes {
+                tracing::info!("Restoring from recovery journal...");
+                let Some(recovered) = read_datafile(prompt, recovered).await else {
+                    return;
+                };
+                match recovered {
+                    Ok((recovered, recovered_key)) => {
+                        datafile.set(recovered);
+                        station.clear_history();
+                        file_handle.set(None);
+                        key.set(recovered_key);
+                        datafile_open.set(true);
+                        needs_saving.set(NeedsSaving(true));
+                    }
+                    Err(e) => {
+                        toast_api.error(
+                            "Failed to recover".to_string(),
+                            ToastOptions::new()
+                                .description(format!("{e}"))
+                                .permanent(false)
+                                .duration(ERROR_DURATION),
+                        );
+                    }
+                }
+            } else if let Err(e) = recovery::clear() {
+                tracing::warn!("Failed to clear recovery journal: {e}");
+            }
+        });
+    });
+
+    use_effect(move || {
+        // Record every unsaved change in the recovery journal, and save to
+        // the chosen file if there is one. The journal is cleared once the
+        // file is saved, as there is then nothing to recover.
+        let datafile = datafile.read();
+        if !*datafile_open.peek() {
+            return;
+        }
+        station.publish(&datafile);
+        if !needs_saving.peek().0 {
+            return;
+        }
+        if let Err(e) = recovery::record(&datafile, key.peek().as_ref()) {
+            tracing::warn!("Failed to record recovery snapshot: {e}");
+        }
+        if let Some(handle) = file_handle.peek().clone() {
+            let data = file_data(&datafile, key.peek().as_ref());
+            spawn(async move {
+                if let Err(e) = handle.write(&data).await {
+                    toast_api.error(
+                        "Failed to save automatically".to_string(),
+                        ToastOptions::new()
+                            .description(format!("{e}"))
+                            .permanent(false)
+                            .duration(ERROR_DURATION),
+                    );
+                } else {
+                    needs_saving.set(NeedsSaving(false));
+                    if let Err(e) = recovery::clear() {
+                        tracing::warn!("Failed to clear recovery journal: {e}");
+                    }
+                    toast_api.success(
+                        "Automatically saved".to_string(),
+                        ToastOptions::new()
+                            .permanent(false)
+                            .duration(Duration::from_secs(2)),
+                    );
+                }
+            });
+        }
+    });
 
     rsx! {
         Navbar { class: "navbar", aria_label: "Navigation",
@@ -48,6 +189,9 @@ pub fn SurplusSale() -> Element {
                             );
                         return;
                     }
+                    if let Err(e) = recovery::clear() {
+                        tracing::warn!("Failed to clear recovery journal: {e}");
+                    }
                     use_navigator().replace(Route::Home {});
                 },
                 "← Menu"
@@ -81,7 +225,10 @@ pub fn SurplusSale() -> Element {
                                 return;
                             }
                             tracing::info!("Creating new...");
+                            file_handle.set(None);
+                            key.set(None);
                             datafile.set(Datafile::new());
+                            station.clear_history();
                             datafile_open.set(true);
                             needs_saving.set(NeedsSaving(true));
                         },
@@ -90,6 +237,94 @@ pub fn SurplusSale() -> Element {
                     NavbarItem {
                         index: 1usize,
                         class: "navbar-item",
+                        value: "new-from-previous".to_string(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| async move {
+                            if needs_saving.read().0 {
+                                toast_api
+                                    .warning(
+                                        "Needs saving".to_string(),
+                                        ToastOptions::new()
+                                            .description(
+                                                "This file needs saving. Please either save it or close it, then try again.",
+                                            )
+                                            .permanent(false)
+                                            .duration(WARNING_DURATION),
+                                    );
+                                return;
+                            }
+                            let Some(paths) = rfd::AsyncFileDialog::new()
+                                .set_title("Select previous auctions to bring unpaid balances forward from")
+                                .add_filter("TDARS auction", &["tdars_auction"])
+                                .pick_files()
+                                .await
+                            else {
+                                return;
+                            };
+                            tracing::info!("Creating new from {} previous...", paths.len());
+                            let mut new_datafile = Datafile::new();
+                            let mut brought_forward = 0;
+                            for (idx, path) in paths.into_iter().enumerate() {
+                                let Some(previous) = read_datafile(prompt, path.read().await).await else {
+                                    return;
+                                };
+                                match previous {
+                                    Ok((previous, _)) => {
+                                        if idx == 0 {
+                                            // Balances are owed in the currency of the first auction
+                                            new_datafile.set_currency(*previous.currency());
+                                        }
+                                        match new_datafile
+                                            .bring_forward_liabilities(&path.file_name(), &previous)
+                                        {
+                                            Ok(count) => brought_forward += count,
+                                            Err(e) => {
+                                                toast_api
+                                                    .warning(
+                                                        format!("Skipped {}", path.file_name()),
+                                                        ToastOptions::new()
+                                                            .description(format!("{e}"))
+                                                            .permanent(false)
+                                                            .duration(WARNING_DURATION),
+                                                    );
+                                            }
+                                        }
+                                    }
+                                    Err(e) => {
+                                        toast_api
+                                            .error(
+                                                format!("Failed to open {}", path.file_name()),
+                                                ToastOptions::new()
+                                                    .description(format!("{e}"))
+                                                    .permanent(false)
+                                                    .duration(ERROR_DURATION),
+                                            );
+                                        return;
+                                    }
+                                }
+                            }
+                            file_handle.set(None);
+                            key.set(None);
+                            datafile.set(new_datafile);
+                            station.clear_history();
+                            datafile_open.set(true);
+                            needs_saving.set(NeedsSaving(true));
+                            toast_api
+                                .info(
+                                    "Unpaid balances brought forward".to_string(),
+                                    ToastOptions::new()
+                                        .description(format!("{brought_forward} unpaid balance(s) brought forward"))
+                                        .permanent(false)
+                                        .duration(INFO_DURATION),
+                                );
+                        },
+                        "New from Previous..."
+                    }
+                    NavbarItem {
+                        index: 2usize,
+                        class: "navbar-item",
                         value: "open".to_string(),
                         to: Route::SurplusSale {},
                         onclick: |_| (),
@@ -115,12 +350,59 @@ pub fn SurplusSale() -> Element {
                                 .pick_file()
                                 .await
                             {
-                                let data = path.read().await;
-                                match serde_json::from_reader::<_, Datafile>(Cursor::new(data)) {
-                                    Ok(datafile_struct) => {
+                                let Some(opened) = read_datafile(prompt, path.read().await).await else {
+                                    return;
+                                };
+                                match opened {
+                                    Ok((mut datafile_struct, opened_key)) => {
+                                        if let Err(e) = datafile_struct.verify_audit_chain() {
+                                            tracing::warn!("Audit log chain broken: {e}");
+                                            rfd::AsyncMessageDialog::new()
+                                                .set_title("Audit log has been tampered with")
+                                                .set_description(
+                                                    format!(
+                                                        "The audit log in this file does not match the record of changes made to it, so this file may have been edited outside of this program: {e}.",
+                                                    ),
+                                                )
+                                                .set_level(rfd::MessageLevel::Warning)
+                                                .set_buttons(rfd::MessageButtons::Ok)
+                                                .show()
+                                                .await;
+                                        }
+                                        let unchained = datafile_struct.unchained_entries();
+                                        if unchained > 0 {
+                                            toast_api
+                                                .warning(
+                                                    "Audit log not verified".to_string(),
+                                                    ToastOptions::new()
+                                                        .description(
+                                                            format!(
+                                                                "The first {unchained} audit log entries were written by an older version, so can't be checked for changes made outside of this program.",
+                                                            ),
+                                                        )
+                                                        .permanent(false)
+                                                        .duration(WARNING_DURATION),
+                                                );
+                                        }
+                                        let Some(repaired) = validate_before_opening(&mut datafile_struct).await else {
+                                            return;
+                                        };
+                                        if repaired > 0 {
+                                            toast_api
+                                                .info(
+                                                    "File repaired".to_string(),
+                                                    ToastOptions::new()
+                                                        .description(format!("{repaired} problem(s) repaired"))
+                                                        .permanent(false)
+                                                        .duration(INFO_DURATION),
+                                                );
+                                        }
+                                        needs_saving.set(NeedsSaving(repaired > 0));
+                                        file_handle.set(Some(path));
+                                        key.set(opened_key);
                                         datafile.set(datafile_struct);
+                                        station.clear_history();
                                         datafile_open.set(true);
-                                        needs_saving.set(NeedsSaving(false));
                                     }
                                     Err(e) => {
                                         toast_api
@@ -138,7 +420,7 @@ pub fn SurplusSale() -> Element {
                         "Open..."
                     }
                     NavbarItem {
-                        index: 2usize,
+                        index: 3usize,
                         class: "navbar-item",
                         value: "save".to_string(),
                         disabled: !datafile_open(),
@@ -148,12 +430,6 @@ pub fn SurplusSale() -> Element {
                         on_select: move |_| async move {
                             tracing::info!("Saving...");
 
-                            #[allow(
-                                // Button is disabled when this doesn't unwrap
-                                clippy::unwrap_used,
-                                reason = "the ability to serialise the datafile is guaranteed"
-                            )]
-                            let data = serde_json::to_vec(&datafile()).unwrap();
                             let date = Local::now().date_naive();
                             if let Some(handle) = rfd::AsyncFileDialog::new()
                                 .add_filter("TDARS auction", &["tdars_auction"])
@@ -161,6 +437,11 @@ pub fn SurplusSale() -> Element {
                                 .save_file()
                                 .await
                             {
+                                let Some(passphrase) = prompt.choose().await else {
+                                    return;
+                                };
+                                let new_key = (!passphrase.is_empty()).then(|| Key::new(&passphrase));
+                                let data = file_data(&datafile.read(), new_key.as_ref());
                                 if let Err(e) = handle.write(&data).await {
                                     toast_api
                                         .error(
@@ -172,6 +453,11 @@ pub fn SurplusSale() -> Element {
                                         );
                                 } else {
                                     needs_saving.set(NeedsSaving(false));
+                                    file_handle.set(Some(handle));
+                                    key.set(new_key);
+                                    if let Err(e) = recovery::clear() {
+                                        tracing::warn!("Failed to clear recovery journal: {e}");
+                                    }
                                 }
                             }
 
@@ -179,7 +465,7 @@ pub fn SurplusSale() -> Element {
                         "Save"
                     }
                     NavbarItem {
-                        index: 3usize,
+                        index: 4usize,
                         class: "navbar-item",
                         value: "configure".to_string(),
                         disabled: !datafile_open(),
@@ -190,7 +476,102 @@ pub fn SurplusSale() -> Element {
                         "Configuration"
                     }
                     NavbarItem {
-                        index: 4usize,
+                        index: 5usize,
+                        class: "navbar-item",
+                        value: "integrity".to_string(),
+                        disabled: !datafile_open(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| {
+                            tracing::info!("Checking integrity...");
+                            let problems = validate(&datafile.read());
+                            let discrepancies = check_integrity(&datafile.read());
+                            report
+                                .set(Report {
+                                    title: "Integrity Check".to_string(),
+                                    summary: if problems.is_empty() && discrepancies.is_empty() {
+                                        "The lots, unpaid amounts and donations in this file all agree with the audit log."
+                                            .to_string()
+                                    } else {
+                                        "The following problems were found in this file, and differences between it and its audit log."
+                                            .to_string()
+                                    },
+                                    lines: problems
+                                        .iter()
+                                        .map(ToString::to_string)
+                                        .chain(discrepancies.iter().map(ToString::to_string))
+                                        .collect(),
+                                });
+                            report_open.set(true);
+                        },
+                        "Integrity Check"
+                    }
+                    NavbarItem {
+                        index: 6usize,
+                        class: "navbar-item",
+                        value: "merge".to_string(),
+                        disabled: !datafile_open(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| async move {
+                            let Some(path) = rfd::AsyncFileDialog::new()
+                                .set_title("Select another copy of this auction to merge in")
+                                .add_filter("TDARS auction", &["tdars_auction"])
+                                .pick_file()
+                                .await
+                            else {
+                                return;
+                            };
+                            tracing::info!("Merging...");
+                            let Some(other) = read_datafile(prompt, path.read().await).await else {
+                                return;
+                            };
+                            let merged = other
+                                .map_err(|e| e.to_string())
+                                .and_then(|(other, _)| {
+                                    datafile
+                                        .read()
+                                        .merge(&other, &path.file_name())
+                                        .map_err(|e| e.to_string())
+                                });
+                            match merged {
+                                Ok((merged, conflicts)) => {
+                                    datafile.set(merged);
+                                    station.clear_history();
+                                    needs_saving.set(NeedsSaving(true));
+                                    report
+                                        .set(Report {
+                                            title: "Merge Report".to_string(),
+                                            summary: if conflicts.is_empty() {
+                                                format!("{} was merged in without any conflicts.", path.file_name())
+                                            } else {
+                                                format!(
+                                                    "{} was merged in, but the following conflicts could not be resolved.",
+                                                    path.file_name(),
+                                                )
+                                            },
+                                            lines: conflicts.iter().map(ToString::to_string).collect(),
+                                        });
+                                    report_open.set(true);
+                                }
+                                Err(e) => {
+                                    toast_api
+                                        .error(
+                                            "Failed to merge".to_string(),
+                                            ToastOptions::new()
+                                                .description(format!("{e}"))
+                                                .permanent(false)
+                                                .duration(ERROR_DURATION),
+                                        );
+                                }
+                            }
+                        },
+                        "Merge..."
+                    }
+                    NavbarItem {
+                        index: 7usize,
                         class: "navbar-item",
                         value: "close".to_string(),
                         disabled: !datafile_open(),
@@ -213,9 +594,16 @@ pub fn SurplusSale() -> Element {
                                 }
                             }
                             tracing::info!("Closing...");
-                            datafile.set(Datafile::new());
+                            station.disconnect();
                             datafile_open.set(false);
+                            file_handle.set(None);
+                            key.set(None);
+                            datafile.set(Datafile::new());
+                            station.clear_history();
                             needs_saving.set(NeedsSaving(false));
+                            if let Err(e) = recovery::clear() {
+                                tracing::warn!("Failed to clear recovery journal: {e}");
+                            }
                         },
                         "Close"
                     }
@@ -226,6 +614,48 @@ pub fn SurplusSale() -> Element {
                 class: "navbar-nav",
                 index: 2usize,
                 disabled: !datafile_open(),
+                NavbarTrigger { class: "navbar-trigger",
+                    "Edit"
+                    NavbarIcon {}
+                }
+                NavbarContent { class: "navbar-content",
+                    NavbarItem {
+                        index: 0usize,
+                        class: "navbar-item",
+                        value: "undo".to_string(),
+                        disabled: station.next_undo().is_none(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| move_through_history(HistoryShortcut::Undo),
+                        if let Some(description) = station.next_undo() {
+                            "Undo {description}"
+                        } else {
+                            "Undo"
+                        }
+                    }
+                    NavbarItem {
+                        index: 1usize,
+                        class: "navbar-item",
+                        value: "redo".to_string(),
+                        disabled: station.next_redo().is_none(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| move_through_history(HistoryShortcut::Redo),
+                        if let Some(description) = station.next_redo() {
+                            "Redo {description}"
+                        } else {
+                            "Redo"
+                        }
+                    }
+                }
+            }
+
+            NavbarNav {
+                class: "navbar-nav",
+                index: 3usize,
+                disabled: !datafile_open(),
                 NavbarTrigger { class: "navbar-trigger",
                     "Export"
                     NavbarIcon {}
@@ -281,15 +711,221 @@ pub fn SurplusSale() -> Element {
                     }
                 }
             }
+
+            NavbarNav { class: "navbar-nav", index: 4usize,
+                NavbarTrigger { class: "navbar-trigger",
+                    "Network"
+                    NavbarIcon {}
+                }
+                NavbarContent { class: "navbar-content",
+                    NavbarItem {
+                        index: 0usize,
+                        class: "navbar-item",
+                        value: "host".to_string(),
+                        disabled: !datafile_open() || station.is_connected(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| async move {
+                            tracing::info!("Hosting on port {DEFAULT_PORT}...");
+                            if let Err(e) = station.host(DEFAULT_PORT).await {
+                                toast_api
+                                    .error(
+                                        "Failed to host".to_string(),
+                                        ToastOptions::new()
+                                            .description(format!("{e}"))
+                                            .permanent(false)
+                                            .duration(ERROR_DURATION),
+                                    );
+                            }
+                        },
+                        "Host on This Network"
+                    }
+                    NavbarItem {
+                        index: 1usize,
+                        class: "navbar-item",
+                        value: "join".to_string(),
+                        disabled: station.is_connected(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| {
+                            if needs_saving.read().0 {
+                                toast_api
+                                    .warning(
+                                        "Needs saving".to_string(),
+                                        ToastOptions::new()
+                                            .description(
+                                                "This file needs saving. Please either save it or close it, then try again.",
+                                            )
+                                            .permanent(false)
+                                            .duration(WARNING_DURATION),
+                                    );
+                                return;
+                            }
+                            join_open.set(true);
+                        },
+                        "Join Station..."
+                    }
+                    NavbarItem {
+                        index: 2usize,
+                        class: "navbar-item",
+                        value: "disconnect".to_string(),
+                        disabled: !station.is_connected(),
+                        to: Route::SurplusSale {},
+                        onclick: |_| (),
+                        onclick_only: true,
+                        on_select: move |_| {
+                            tracing::info!("Disconnecting...");
+                            station.disconnect();
+                        },
+                        "Disconnect"
+                    }
+                    if cfg!(feature = "phone-entry") {
+                        NavbarItem {
+                            index: 3usize,
+                            class: "navbar-item",
+                            value: "phone-entry".to_string(),
+                            disabled: !datafile_open(),
+                            to: Route::SurplusSale {},
+                            onclick: |_| (),
+                            onclick_only: true,
+                            on_select: move |_| phone_entry_open.set(true),
+                            "Phone Entry..."
+                        }
+                    }
+                }
+            }
+
+            span { class: "navbar-status", "{sync_status}" }
         }
 
         h2 { font_size: "1rem", "Surplus Sale" }
 
         if datafile_open() {
             LoadedFile { loaded_file: datafile, configure_open }
+            ReportDialog { open: report_open, report: report() }
+            PhoneEntryDialog { open: phone_entry_open }
         } else {
             "Nothing open..."
         }
+
+        PassphraseDialog { prompt }
+
+        JoinStation {
+            open: join_open,
+            on_join: move |(address, pin): (String, String)| async move {
+                tracing::info!("Joining {address}...");
+                match station.join(&address, &pin).await {
+                    Ok(()) => {
+                        station.clear_history();
+                        file_handle.set(None);
+                        key.set(None);
+                        datafile_open.set(true);
+                    }
+                    Err(e) => {
+                        toast_api
+                            .error(
+                                format!("Failed to join {address}"),
+                                ToastOptions::new()
+                                    .description(format!("{e}"))
+                                    .permanent(false)
+                                    .duration(ERROR_DURATION),
+                            );
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// The reasons a surplus sale file can fail to open
+#[derive(Display)]
+enum OpenError {
+    #[display("{_0}")]
+    Decrypt(DecryptError),
+    #[display("{_0}")]
+    Load(LoadError),
+}
+
+/// Read a surplus sale file, asking for its passphrase if it is
+/// encrypted. Returns the datafile and the key it was encrypted with, or
+/// `None` if the user cancels.
+async fn read_datafile(
+    prompt: PassphrasePrompt,
+    data: Vec<u8>,
+) -> Option<Result<(Datafile, Option<Key>), OpenError>> {
+    let (data, key) = if encryption::is_encrypted(&data) {
+        let passphrase = prompt.enter().await?;
+        match encryption::open(&data, &passphrase) {
+            Ok((key, data)) => (data, Some(key)),
+            Err(e) => return Some(Err(OpenError::Decrypt(e))),
+        }
+    } else {
+        (data, None)
+    };
+    Some(
+        Datafile::load(&data)
+            .map(|datafile| (datafile, key))
+            .map_err(OpenError::Load),
+    )
+}
+
+/// Check a datafile being opened for problems, and offer to repair any
+/// that can be repaired automatically before it is opened. Returns the
+/// number of problems repaired, or `None` if it should not be opened.
+async fn validate_before_opening(datafile: &mut Datafile) -> Option<usize> {
+    let problems = validate(datafile);
+    if problems.is_empty() {
+        return Some(0);
+    }
+    tracing::warn!("{} problem(s) found in the file", problems.len());
+    let list = problems
+        .iter()
+        .map(|p| format!("• {p}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let repairable = problems.iter().filter(|p| p.is_repairable()).count();
+    let dialog = rfd::AsyncMessageDialog::new()
+        .set_title("Problems found in this file")
+        .set_level(rfd::MessageLevel::Warning);
+    if repairable == 0 {
+        let response = dialog
+            .set_description(format!(
+                "The following problems were found in this file, and can't be repaired automatically:\n\n{list}\n\nDo you want to open it anyway?",
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show()
+            .await;
+        return (response == rfd::MessageDialogResult::Yes).then_some(0);
+    }
+    let response = dialog
+        .set_description(format!(
+            "The following problems were found in this file:\n\n{list}\n\n{repairable} of these can be repaired automatically. Do you want to repair them before opening the file? Choose No to open it as it is.",
+        ))
+        .set_buttons(rfd::MessageButtons::YesNoCancel)
+        .show()
+        .await;
+    match response {
+        rfd::MessageDialogResult::Yes => {
+            Some(problems.iter().filter(|p| datafile.repair(p)).count())
+        }
+        rfd::MessageDialogResult::No => Some(0),
+        _ => None,
+    }
+}
+
+/// Serialise a datafile to be saved, encrypted with the key if there is
+/// one.
+fn file_data(datafile: &Datafile, key: Option<&Key>) -> Vec<u8> {
+    #[allow(
+        clippy::unwrap_used,
+        reason = "the format is guaranteed to be serializable"
+    )]
+    let data = serde_json::to_vec(datafile).unwrap();
+    match key {
+        Some(key) => key.seal(&data),
+        None => data,
     }
 }
 
@@ -0,0 +1,166 @@
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use derive_more::Display;
+use sha2::{Digest, Sha256};
+
+/// The bytes every encrypted datafile starts with
+const MAGIC: &[u8; 8] = b"TDARSENC";
+
+/// The version of the encrypted file format
+const FORMAT_VERSION: u8 = 1;
+
+/// The length of the salt the key is derived with
+const SALT_LEN: usize = 16;
+
+/// The length of the check that the passphrase is right
+const CHECK_LEN: usize = 32;
+
+/// The length of the nonce each piece of data is encrypted with
+const NONCE_LEN: usize = 24;
+
+/// The length of the header of an encrypted file, before its data
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + CHECK_LEN;
+
+/// The reasons an encrypted datafile can fail to decrypt
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum DecryptError {
+    #[display("this file is encrypted, so needs its passphrase to open")]
+    PassphraseRequired,
+    #[display("the passphrase is wrong")]
+    WrongPassphrase,
+    #[display("this file is corrupt: the encrypted data has been damaged or altered")]
+    Corrupt,
+    #[display("this file was encrypted by a newer version of the toolkit. Please update the toolkit to open it")]
+    NewerVersion,
+}
+
+impl std::error::Error for DecryptError {}
+
+/// A key derived from a passphrase, used to encrypt and decrypt a
+/// datafile and anything stored alongside it.
+///
+/// The key is derived with Argon2id from the passphrase and a random
+/// salt, and data is encrypted with XChaCha20-Poly1305, so any change to
+/// the encrypted data is detected when decrypting. Half of the derived
+/// key material is hashed and stored in the header, so that a wrong
+/// passphrase can be told apart from a corrupt file.
+#[derive(Clone)]
+pub struct Key {
+    cipher: XChaCha20Poly1305,
+    salt: [u8; SALT_LEN],
+    check: [u8; CHECK_LEN],
+}
+
+impl Key {
+    /// Derive a new key from a passphrase, with a new salt.
+    ///
+    /// # Panics
+    ///
+    /// Never, as the salt and key lengths are always valid for Argon2.
+    #[must_use]
+    pub fn new(passphrase: &str) -> Self {
+        let mut salt = [0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        #[allow(
+            clippy::unwrap_used,
+            reason = "the salt and key lengths are always valid for Argon2"
+        )]
+        Self::derive(passphrase, salt).unwrap()
+    }
+
+    /// Derive the key from a passphrase and salt.
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Self, argon2::Error> {
+        let mut material = [0; 64];
+        Argon2::default().hash_password_into(passphrase.as_bytes(), &salt, &mut material)?;
+        let (key, check) = material.split_at(32);
+        Ok(Self {
+            cipher: XChaCha20Poly1305::new(key.into()),
+            salt,
+            check: Sha256::digest(check).into(),
+        })
+    }
+
+    /// Encrypt data with a new random nonce, which is stored before the
+    /// encrypted data.
+    ///
+    /// # Panics
+    ///
+    /// If the data is far larger than any datafile.
+    #[must_use]
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        #[allow(
+            clippy::unwrap_used,
+            reason = "encrypting only fails for data far larger than any datafile"
+        )]
+        let encrypted = self.cipher.encrypt(&nonce, data).unwrap();
+        let mut sealed = nonce.to_vec();
+        sealed.extend(encrypted);
+        sealed
+    }
+
+    /// Decrypt data encrypted by [`Key::encrypt`].
+    ///
+    /// # Errors
+    ///
+    /// If the data has been damaged or altered, or was encrypted with
+    /// another key.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        if data.len() < NONCE_LEN {
+            return Err(DecryptError::Corrupt);
+        }
+        let (nonce, encrypted) = data.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), encrypted)
+            .map_err(|_| DecryptError::Corrupt)
+    }
+
+    /// Encrypt a whole datafile, with the header needed to decrypt it
+    /// again with the passphrase.
+    #[must_use]
+    pub fn seal(&self, data: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::with_capacity(HEADER_LEN + NONCE_LEN + data.len() + 16);
+        sealed.extend(MAGIC);
+        sealed.push(FORMAT_VERSION);
+        sealed.extend(self.salt);
+        sealed.extend(self.check);
+        sealed.extend(self.encrypt(data));
+        sealed
+    }
+}
+
+/// Whether a datafile is encrypted.
+#[must_use]
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Decrypt a whole datafile encrypted by [`Key::seal`], returning the
+/// key so that it can be saved again with the same passphrase.
+///
+/// # Errors
+///
+/// If the passphrase is wrong, the file is corrupt, or it was encrypted
+/// by a newer version.
+pub fn open(data: &[u8], passphrase: &str) -> Result<(Key, Vec<u8>), DecryptError> {
+    if data.len() < HEADER_LEN || !is_encrypted(data) {
+        return Err(DecryptError::Corrupt);
+    }
+    let (header, encrypted) = data.split_at(HEADER_LEN);
+    if header[MAGIC.len()] > FORMAT_VERSION {
+        return Err(DecryptError::NewerVersion);
+    }
+    let (salt, check) = header[MAGIC.len() + 1..].split_at(SALT_LEN);
+
+    let mut salt_bytes = [0; SALT_LEN];
+    salt_bytes.copy_from_slice(salt);
+    let key = Key::derive(passphrase, salt_bytes).map_err(|_| DecryptError::Corrupt)?;
+    if key.check[..] != *check {
+        return Err(DecryptError::WrongPassphrase);
+    }
+    let data = key.decrypt(encrypted)?;
+    Ok((key, data))
+}
@@ -1,9 +1,13 @@
-use crate::hamfest_table::types::ReceiptLine;
+use crate::{
+    hamfest_table::types::ReceiptLine,
+    spreadsheet::{self, ExportError},
+};
 
 use super::types::Datafile;
 
-use bigdecimal::ToPrimitive;
-use rust_xlsxwriter::{Format, FormatBorder, Formula, Workbook, XlsxError};
+use bigdecimal::BigDecimal;
+use iso_currency::Currency;
+use rust_xlsxwriter::{Format, FormatBorder, Formula, Workbook};
 
 #[allow(
     clippy::unreadable_literal,
@@ -18,13 +22,21 @@ const COL_DEBIT: u16 = 5;
 const COL_CREDIT: u16 = 6;
 const COL_BAL: u16 = 7;
 
-pub fn export(datafile: &Datafile) -> Result<Vec<u8>, XlsxError> {
+/// Export the receipts taken in a session to an Excel workbook.
+///
+/// Amounts are rounded to the penny, see [`spreadsheet::round`].
+///
+/// # Errors
+///
+/// If an amount can't be written exactly, or the workbook could not be
+/// written.
+pub fn export(datafile: &Datafile) -> Result<Vec<u8>, ExportError> {
     let mut workbook = Workbook::new();
 
     // Transactions
     create_transactions_sheet(&mut workbook, datafile)?;
 
-    workbook.save_to_buffer()
+    Ok(workbook.save_to_buffer()?)
 }
 
 #[allow(
@@ -34,7 +46,7 @@ pub fn export(datafile: &Datafile) -> Result<Vec<u8>, XlsxError> {
 fn create_transactions_sheet(
     workbook: &mut Workbook,
     datafile: &Datafile,
-) -> Result<(), XlsxError> {
+) -> Result<(), ExportError> {
     let title_format = Format::new().set_bold().set_font_size(28.);
     let table_heading_format = Format::new()
         .set_bold()
@@ -46,8 +58,11 @@ fn create_transactions_sheet(
         .clone()
         .set_background_color(ALT_BG);
 
-    let accounting_format = Format::new().set_num_format("[$£-809]#,##0.00;[RED]-[$£-809]#,##0.00");
+    // Club tables are always held in pounds
+    let accounting_format = Format::new().set_num_format(spreadsheet::money_format(Currency::GBP));
     let accounting_alt_format = accounting_format.clone().set_background_color(ALT_BG);
+    let minor_units = Currency::GBP.exponent();
+    let money = |amount: &BigDecimal| spreadsheet::cell_value(amount, minor_units);
 
     let worksheet = workbook
         .add_worksheet()
@@ -112,11 +127,7 @@ fn create_transactions_sheet(
                 row,
                 COL_DEBIT,
                 match line {
-                    ReceiptLine::Change { amount, .. } =>
-                    {
-                        #[allow(clippy::unwrap_used, reason = "excel needs to deal with it!")]
-                        amount.to_f64().unwrap()
-                    }
+                    ReceiptLine::Change { amount, .. } => money(amount)?,
                     _ => 0.,
                 },
                 fmt_acc,
@@ -126,11 +137,7 @@ fn create_transactions_sheet(
                 row,
                 COL_CREDIT,
                 match line {
-                    ReceiptLine::Payment { amount, .. } =>
-                    {
-                        #[allow(clippy::unwrap_used, reason = "excel needs to deal with it!")]
-                        amount.to_f64().unwrap()
-                    }
+                    ReceiptLine::Payment { amount, .. } => money(amount)?,
                     _ => 0.,
                 },
                 fmt_acc,
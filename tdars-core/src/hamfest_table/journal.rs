@@ -0,0 +1,349 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    encryption::{self, DecryptError, Key},
+    hamfest_table::types::{Datafile, Item, Receipt},
+    schema::LoadError,
+};
+
+/// The number of events appended to the journal before it is compacted
+/// into the snapshot
+const COMPACT_AFTER: usize = 100;
+
+/// A change to a datafile, recorded in the journal
+#[derive(Serialize, Deserialize)]
+enum JournalEvent {
+    /// An item was created or its details changed
+    ItemSaved { item: Item },
+    /// A receipt was completed
+    ReceiptSaved { receipt: Receipt },
+    /// An item was removed
+    ItemRemoved { barcode: String },
+    /// A receipt was removed
+    ReceiptRemoved { number: Uuid },
+}
+
+impl JournalEvent {
+    /// Apply this event to a datafile. Applying an event more than once
+    /// has no further effect.
+    fn apply(self, datafile: &mut Datafile) {
+        match self {
+            Self::ItemSaved { item } => {
+                if let Some(existing) = datafile
+                    .items_mut()
+                    .iter_mut()
+                    .find(|i| i.barcode() == item.barcode())
+                {
+                    *existing = item;
+                } else {
+                    datafile.items_mut().push(item);
+                }
+            }
+            Self::ReceiptSaved { receipt } => {
+                if !datafile
+                    .receipts()
+                    .iter()
+                    .any(|r| r.number() == receipt.number())
+                {
+                    datafile.receipts_mut().push(receipt);
+                }
+            }
+            Self::ItemRemoved { barcode } => {
+                datafile.items_mut().retain(|i| *i.barcode() != barcode);
+            }
+            Self::ReceiptRemoved { number } => {
+                datafile.receipts_mut().retain(|r| *r.number() != number);
+            }
+        }
+    }
+}
+
+/// The reasons a journalled datafile can fail to open
+#[derive(Debug, Display)]
+pub enum OpenError {
+    #[display("{_0}")]
+    Io(io::Error),
+    #[display("{_0}")]
+    Load(LoadError),
+    #[display("{_0}")]
+    Decrypt(DecryptError),
+    #[display("the journal is corrupt at record {record}: {reason}")]
+    CorruptRecord { record: usize, reason: String },
+}
+
+impl From<io::Error> for OpenError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<LoadError> for OpenError {
+    fn from(value: LoadError) -> Self {
+        Self::Load(value)
+    }
+}
+
+impl From<DecryptError> for OpenError {
+    fn from(value: DecryptError) -> Self {
+        Self::Decrypt(value)
+    }
+}
+
+/// Stores a datafile as a snapshot, with an append-only journal of the
+/// changes made since the snapshot was taken alongside it. Each change
+/// only appends a small record, rather than rewriting the whole file, and
+/// the journal is periodically compacted into a new snapshot.
+///
+/// If the datafile is encrypted, the snapshot and each record in the
+/// journal are encrypted with the same key.
+pub struct Journal {
+    /// The path of the snapshot
+    path: PathBuf,
+    /// The items as of the last record, to find which have changed
+    items: HashMap<String, Item>,
+    /// The numbers of the receipts as of the last record
+    receipts: HashSet<Uuid>,
+    /// The number of events appended since the last snapshot
+    events: usize,
+    /// The key the datafile is encrypted with, if it is encrypted
+    key: Option<Key>,
+}
+
+impl Journal {
+    /// Create a new journalled datafile at the path, replacing anything
+    /// already there, encrypted with the key if there is one.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot or journal can't be written.
+    pub fn create(path: PathBuf, datafile: &Datafile, key: Option<Key>) -> io::Result<Self> {
+        let mut journal = Self {
+            path,
+            items: HashMap::new(),
+            receipts: HashSet::new(),
+            events: 0,
+            key,
+        };
+        journal.compact(datafile)?;
+        Ok(journal)
+    }
+
+    /// Open a journalled datafile, replaying the journal onto the
+    /// snapshot and then compacting it. See [`Journal::read`].
+    ///
+    /// # Errors
+    ///
+    /// If the datafile can't be read, or can't be compacted.
+    pub fn open(path: PathBuf, passphrase: Option<&str>) -> Result<(Self, Datafile), OpenError> {
+        let (datafile, key) = Self::read(&path, passphrase)?;
+        let journal = Self::create(path, &datafile, key)?;
+        Ok((journal, datafile))
+    }
+
+    /// Whether the journalled datafile at the path is encrypted, so
+    /// needs a passphrase to read.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot can't be read.
+    pub fn is_encrypted(path: &Path) -> io::Result<bool> {
+        Ok(encryption::is_encrypted(&fs::read(path)?))
+    }
+
+    /// Read a journalled datafile without modifying it, decrypting it
+    /// with the passphrase if it is encrypted. Returns the datafile, and
+    /// the key it was encrypted with.
+    ///
+    /// If the last record in the journal is incomplete or corrupt, for
+    /// example because the program stopped while writing it, it is
+    /// ignored. Any other corrupt record is an error.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot or journal can't be read, decrypted or parsed.
+    pub fn read(
+        path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<(Datafile, Option<Key>), OpenError> {
+        let data = fs::read(path)?;
+        let (key, data) = if encryption::is_encrypted(&data) {
+            let passphrase = passphrase.ok_or(DecryptError::PassphraseRequired)?;
+            let (key, data) = encryption::open(&data, passphrase)?;
+            (Some(key), data)
+        } else {
+            (None, data)
+        };
+        let mut datafile = Datafile::load(&data)?;
+
+        let records = match fs::read_to_string(journal_path(path)) {
+            Ok(records) => records,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let records = records.split_terminator('\n').collect::<Vec<_>>();
+        for (idx, record) in records.iter().enumerate() {
+            match decode_record(record, key.as_ref()) {
+                Ok(event) => event.apply(&mut datafile),
+                Err(e) if idx + 1 == records.len() => {
+                    tracing::warn!("Ignoring torn final journal record: {e}");
+                }
+                Err(reason) => {
+                    return Err(OpenError::CorruptRecord {
+                        record: idx + 1,
+                        reason,
+                    });
+                }
+            }
+        }
+        Ok((datafile, key))
+    }
+
+    /// Append the changes made to the datafile since the last record to
+    /// the journal, compacting it if it has grown long enough.
+    ///
+    /// # Errors
+    ///
+    /// If the journal or snapshot can't be written.
+    pub fn record(&mut self, datafile: &Datafile) -> io::Result<()> {
+        let mut events = vec![];
+        for item in datafile.items() {
+            if self.items.get(item.barcode()) != Some(item) {
+                events.push(JournalEvent::ItemSaved { item: item.clone() });
+            }
+        }
+        for barcode in self.items.keys() {
+            if !datafile.items().iter().any(|i| i.barcode() == barcode) {
+                events.push(JournalEvent::ItemRemoved {
+                    barcode: barcode.clone(),
+                });
+            }
+        }
+        for number in &self.receipts {
+            if !datafile.receipts().iter().any(|r| r.number() == number) {
+                events.push(JournalEvent::ReceiptRemoved { number: *number });
+            }
+        }
+        for receipt in datafile.receipts() {
+            if !self.receipts.contains(receipt.number()) {
+                events.push(JournalEvent::ReceiptSaved {
+                    receipt: receipt.clone(),
+                });
+            }
+        }
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        if self.events + events.len() > COMPACT_AFTER {
+            return self.compact(datafile);
+        }
+
+        let mut data = vec![];
+        for event in &events {
+            let record = serde_json::to_vec(event)?;
+            match &self.key {
+                Some(key) => data.extend(encode_hex(&key.encrypt(&record)).into_bytes()),
+                None => data.extend(record),
+            }
+            data.push(b'\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_path(&self.path))?;
+        file.write_all(&data)?;
+        file.sync_data()?;
+
+        self.events += events.len();
+        self.remember(datafile);
+        Ok(())
+    }
+
+    /// Write the whole datafile as a new snapshot and empty the journal.
+    ///
+    /// The snapshot is written to a temporary file which then replaces
+    /// the old snapshot, so an interrupted write never leaves a truncated
+    /// snapshot. Should the program stop before the journal is emptied,
+    /// replaying it onto the new snapshot has no effect.
+    ///
+    /// # Errors
+    ///
+    /// If the snapshot or journal can't be written.
+    pub fn compact(&mut self, datafile: &Datafile) -> io::Result<()> {
+        let temp = self.path.with_extension("tmp");
+        let mut data = serde_json::to_vec(datafile)?;
+        if let Some(key) = &self.key {
+            data = key.seal(&data);
+        }
+        let mut file = File::create(&temp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+
+        File::create(journal_path(&self.path))?.sync_all()?;
+
+        self.events = 0;
+        self.remember(datafile);
+        Ok(())
+    }
+
+    /// Remember the state of the datafile as recorded.
+    fn remember(&mut self, datafile: &Datafile) {
+        self.items = datafile
+            .items()
+            .iter()
+            .map(|i| (i.barcode().clone(), i.clone()))
+            .collect();
+        self.receipts = datafile.receipts().iter().map(|r| *r.number()).collect();
+    }
+}
+
+/// Decode a record in the journal, which is hex encoded if encrypted.
+fn decode_record(record: &str, key: Option<&Key>) -> Result<JournalEvent, String> {
+    let record = match key {
+        Some(key) => {
+            let encrypted = decode_hex(record).ok_or("the record is not encrypted")?;
+            key.decrypt(&encrypted).map_err(|e| e.to_string())?
+        }
+        None => record.as_bytes().to_vec(),
+    };
+    serde_json::from_slice(&record).map_err(|e| e.to_string())
+}
+
+/// Encode data as lowercase hexadecimal.
+fn encode_hex(data: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(data.len() * 2);
+    for byte in data {
+        hex.push(char::from(DIGITS[usize::from(byte >> 4)]));
+        hex.push(char::from(DIGITS[usize::from(byte & 0xf)]));
+    }
+    hex
+}
+
+/// Decode hexadecimal, returning `None` if it is not valid.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// The path of the journal kept alongside a snapshot
+fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".journal");
+    PathBuf::from(name)
+}
@@ -0,0 +1,5 @@
+//! Club tables, where the club sells its own items at a hamfest.
+
+pub mod export;
+pub mod journal;
+pub mod types;
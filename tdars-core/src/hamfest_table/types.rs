@@ -0,0 +1,212 @@
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Local};
+use derive_more::Display;
+use getset::{Getters, MutGetters, Setters};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::schema::{self, LoadError, Migration};
+
+#[derive(Serialize, Deserialize, PartialEq, Getters, MutGetters)]
+#[getset(get = "pub", get_mut = "pub")]
+pub struct Datafile {
+    /// The schema version of this file
+    #[getset(skip)]
+    version: u64,
+    items: Vec<Item>,
+    receipts: Vec<Receipt>,
+}
+
+impl Default for Datafile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Datafile {
+    /// Create a new datafile
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            version: schema::current_version(MIGRATIONS),
+            items: vec![],
+            receipts: vec![],
+        }
+    }
+
+    /// Load a datafile from its serialised form, migrating it from any
+    /// older version of the file.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be parsed, or was written by a newer version.
+    pub fn load(data: &[u8]) -> Result<Self, LoadError> {
+        let value = schema::migrate(data, MIGRATIONS)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Merge another session, for example one recorded on a separate
+    /// laptop, into a new datafile. Items are matched by barcode and
+    /// receipts by number. Where both sessions hold different versions of
+    /// the same item or receipt, this session's is kept and the conflict
+    /// is listed in the returned report.
+    #[must_use]
+    pub fn merge(&self, other: &Datafile) -> (Datafile, Vec<MergeConflict>) {
+        let mut conflicts = vec![];
+        let mut items = self.items.clone();
+        for theirs in &other.items {
+            match items.iter().find(|i| i.barcode == theirs.barcode) {
+                None => items.push(theirs.clone()),
+                Some(ours) if ours != theirs => {
+                    conflicts.push(MergeConflict::ItemDiffers {
+                        ours: Box::new(ours.clone()),
+                        theirs: Box::new(theirs.clone()),
+                    });
+                }
+                Some(_) => (),
+            }
+        }
+
+        let mut receipts = self.receipts.clone();
+        for theirs in &other.receipts {
+            match receipts.iter().find(|r| r.number == theirs.number) {
+                None => receipts.push(theirs.clone()),
+                Some(ours) if ours != theirs => {
+                    conflicts.push(MergeConflict::ReceiptDiffers {
+                        number: theirs.number,
+                    });
+                }
+                Some(_) => (),
+            }
+        }
+        receipts.sort_by_key(|r| r.timestamp);
+
+        (
+            Self {
+                version: self.version,
+                items,
+                receipts,
+            },
+            conflicts,
+        )
+    }
+}
+
+/// A difference between two sessions which could not be resolved when
+/// merging them
+#[derive(Display)]
+pub enum MergeConflict {
+    #[display("The item {ours} in this session is {theirs} in the other; kept {ours}")]
+    ItemDiffers { ours: Box<Item>, theirs: Box<Item> },
+    #[display("Receipt {number} differs between the sessions; kept this session's")]
+    ReceiptDiffers { number: Uuid },
+}
+
+/// Migrations from every previous version of the datafile, see
+/// [`schema::migrate`].
+const MIGRATIONS: &[Migration] = &[migrate_versioned];
+
+/// Version 0 to 1: files written before versions were recorded are
+/// otherwise unchanged.
+#[allow(
+    clippy::unnecessary_wraps,
+    reason = "all migrations share the same signature"
+)]
+fn migrate_versioned(_file: &mut Map<String, Value>) -> serde_json::Result<()> {
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Getters, Setters, Display, Default, Clone)]
+#[getset(get = "pub")]
+#[display("{name} ({price})")]
+pub struct Item {
+    barcode: String,
+    #[getset(set = "pub")]
+    name: String,
+    #[getset(set = "pub")]
+    description: String,
+    #[getset(set = "pub")]
+    price: BigDecimal,
+}
+
+impl Item {
+    /// Create a new empty item with just a barcode
+    #[must_use]
+    pub fn new(barcode: String) -> Self {
+        Self {
+            barcode,
+            name: String::new(),
+            description: String::new(),
+            price: BigDecimal::zero(),
+        }
+    }
+}
+
+/// Documents represent both receipts and payments
+#[derive(Serialize, Deserialize, PartialEq, Getters, MutGetters, Clone)]
+#[getset(get = "pub")]
+pub struct Receipt {
+    timestamp: DateTime<Local>,
+    number: Uuid,
+    #[getset(get_mut = "pub")]
+    lines: Vec<ReceiptLine>,
+}
+
+impl Default for Receipt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Receipt {
+    /// Create a new receipt
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            timestamp: Local::now(),
+            number: Uuid::new_v4(),
+            lines: vec![],
+        }
+    }
+
+    /// Calculate the total of this receipt
+    #[must_use]
+    pub fn total(&self) -> BigDecimal {
+        let mut total = BigDecimal::zero();
+        for line in self.lines() {
+            match line {
+                ReceiptLine::Item { item } => total += item.price(),
+                ReceiptLine::Payment { amount, .. } => total -= amount,
+                ReceiptLine::Change { amount, .. } => total += amount,
+            }
+        }
+        total
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Display, Clone)]
+pub enum ReceiptLine {
+    Item {
+        item: Item,
+    },
+    #[display("Payment via {method}")]
+    Payment {
+        method: TransactionMethod,
+        amount: BigDecimal,
+    },
+    #[display("Change via {method}")]
+    Change {
+        method: TransactionMethod,
+        amount: BigDecimal,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Display, Clone, Copy)]
+pub enum TransactionMethod {
+    Cash,
+    Card,
+    #[display("Bank Transfer")]
+    BankTransfer,
+    Cheque,
+}
@@ -0,0 +1,15 @@
+//! The file formats and money logic of the TDARS Toolkit, without any
+//! user interface, so that other tools can read and write its files.
+//!
+//! Log messages are emitted through [`tracing`], so are only shown if the
+//! program using this crate installs a subscriber.
+#![warn(clippy::pedantic)]
+#![deny(clippy::expect_used)]
+#![deny(clippy::unwrap_used)]
+
+pub mod encryption;
+pub mod hamfest_table;
+pub mod schema;
+pub mod spreadsheet;
+pub mod surplus_sale;
+pub mod types;
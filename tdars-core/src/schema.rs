@@ -0,0 +1,78 @@
+use derive_more::Display;
+use serde::de::Error as _;
+use serde_json::{Map, Value};
+
+/// A function which migrates a datafile, as a JSON object, from one
+/// version of the schema to the next.
+pub type Migration = fn(&mut Map<String, Value>) -> serde_json::Result<()>;
+
+/// The key that the schema version is stored under in each datafile
+const VERSION_KEY: &str = "version";
+
+/// The reasons a datafile can fail to load
+#[derive(Debug, Display)]
+pub enum LoadError {
+    /// The file was written by a newer version of this program, so may
+    /// hold data this version does not understand
+    #[display(
+        "this file was written by a newer version of the toolkit (file version {found}, this version supports up to {supported}). Please update the toolkit to open it"
+    )]
+    NewerVersion { found: u64, supported: u64 },
+    /// The file is not a valid datafile
+    #[display("this file is corrupt or is not a datafile: {_0}")]
+    Corrupt(serde_json::Error),
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Corrupt(value)
+    }
+}
+
+/// The current schema version, given the migrations from every previous
+/// version.
+#[must_use]
+pub const fn current_version(migrations: &[Migration]) -> u64 {
+    migrations.len() as u64
+}
+
+/// Parse a datafile and migrate it up to the current schema version.
+///
+/// `migrations[n]` migrates a file from version `n` to version `n + 1`,
+/// so the current version is the number of migrations. Files written
+/// before versions were recorded are version 0.
+///
+/// # Errors
+///
+/// If the file can't be parsed or migrated, or was written by a newer
+/// version.
+pub fn migrate(data: &[u8], migrations: &[Migration]) -> Result<Value, LoadError> {
+    let mut value: Value = serde_json::from_slice(data)?;
+    let supported = current_version(migrations);
+    let Some(file) = value.as_object_mut() else {
+        return Err(serde_json::Error::custom("expected a datafile object").into());
+    };
+
+    let found = match file.get(VERSION_KEY) {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| serde_json::Error::custom("invalid file version"))?,
+    };
+    if found > supported {
+        return Err(LoadError::NewerVersion { found, supported });
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "there will never be enough migrations to truncate"
+    )]
+    for migration in &migrations[found as usize..] {
+        migration(file)?;
+    }
+    file.insert(VERSION_KEY.to_string(), Value::from(supported));
+
+    Ok(value)
+}
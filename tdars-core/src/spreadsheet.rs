@@ -0,0 +1,88 @@
+//! Writing amounts of money to spreadsheets exactly.
+
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use derive_more::Display;
+use iso_currency::Currency;
+use rust_xlsxwriter::XlsxError;
+
+/// The number of significant digits a spreadsheet keeps of a number
+const SIGNIFICANT_DIGITS: u64 = 15;
+
+/// The reasons a workbook can fail to export
+#[derive(Debug, Display)]
+pub enum ExportError {
+    /// An amount has more digits than a spreadsheet can hold exactly
+    #[display("{_0} has too many digits to be written to a spreadsheet exactly")]
+    Unrepresentable(BigDecimal),
+    /// There are more rows than fit in a worksheet
+    #[display("there are too many rows to fit in a worksheet")]
+    TooManyRows,
+    /// The workbook itself could not be written
+    #[display("failed to write the workbook: {_0}")]
+    Xlsx(XlsxError),
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<XlsxError> for ExportError {
+    fn from(value: XlsxError) -> Self {
+        Self::Xlsx(value)
+    }
+}
+
+/// The number format for amounts of money in the currency, showing its
+/// symbol and a decimal place for each digit of its minor unit, with
+/// negative amounts in red.
+#[must_use]
+pub fn money_format(currency: Currency) -> String {
+    let decimals = match currency.exponent() {
+        Some(places) if places > 0 => format!(".{}", "0".repeat(places.into())),
+        _ => String::new(),
+    };
+    let amount = format!("[${}]#,##0{decimals}", currency.symbol());
+    format!("{amount};[RED]-{amount}")
+}
+
+/// Round an amount of money to the minor unit of its currency, e.g. to
+/// the penny for pounds, with halves rounded away from zero as they would
+/// be by hand. Amounts in currencies without a minor unit are left as
+/// they are.
+///
+/// Each amount is rounded on its own, so that totals in a ledger add up
+/// to the amounts shown above them.
+#[must_use]
+pub fn round(amount: &BigDecimal, minor_units: Option<u16>) -> BigDecimal {
+    match minor_units {
+        Some(places) => amount.with_scale_round(places.into(), RoundingMode::HalfUp),
+        None => amount.clone(),
+    }
+}
+
+/// The value to write to a cell for an amount of money, rounded as by
+/// [`round`].
+///
+/// Spreadsheets hold numbers in binary floating point, which can't hold
+/// most decimal amounts exactly. The value returned is the closest to the
+/// rounded amount, so is written to the workbook as the rounded amount
+/// itself, and reads back as exactly that.
+///
+/// # Errors
+///
+/// If the rounded amount has more significant digits than a spreadsheet
+/// keeps.
+pub fn cell_value(amount: &BigDecimal, minor_units: Option<u16>) -> Result<f64, ExportError> {
+    let rounded = round(amount, minor_units);
+    let unrepresentable = || ExportError::Unrepresentable(rounded.clone());
+    if rounded.digits() > SIGNIFICANT_DIGITS {
+        return Err(unrepresentable());
+    }
+    let value = f64::from_str(&rounded.to_string()).map_err(|_| unrepresentable())?;
+    // Numbers are written as the shortest decimal that reads back as the
+    // same value
+    if BigDecimal::from_str(&value.to_string()).ok() != Some(rounded.clone()) {
+        return Err(unrepresentable());
+    }
+    Ok(value)
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use bigdecimal::{BigDecimal, Zero};
+use getset::{Getters, MutGetters, Setters, WithSetters};
+use serde::{Deserialize, Serialize};
+
+use crate::types::Callsign;
+
+/// The rules used to calculate the commission the club takes from each
+/// lot sold.
+#[derive(
+    Serialize, Deserialize, Clone, PartialEq, Default, Getters, MutGetters, Setters, WithSetters,
+)]
+#[getset(get = "pub", get_mut = "pub", set = "pub", set_with = "pub")]
+pub struct CommissionRules {
+    /// Percentage bands by hammer price. The band with the highest
+    /// starting price at or below the hammer price applies to the whole
+    /// hammer price.
+    bands: Vec<CommissionBand>,
+    /// The minimum commission taken from a lot, if any
+    minimum_fee: Option<BigDecimal>,
+    /// The maximum commission taken from a lot, if any
+    maximum_fee: Option<BigDecimal>,
+    /// A fixed fee taken from every lot sold, on top of the commission
+    entry_fee: BigDecimal,
+    /// Overrides of these rules for individual sellers
+    overrides: Vec<(Callsign, CommissionOverride)>,
+}
+
+impl CommissionRules {
+    /// Create a set of rules charging a single rate on every lot.
+    #[must_use]
+    pub fn flat(rate: BigDecimal) -> Self {
+        Self {
+            bands: vec![CommissionBand::new(BigDecimal::zero(), rate)],
+            ..Default::default()
+        }
+    }
+
+    /// Return the override for the seller, if any.
+    #[must_use]
+    pub fn override_for(&self, seller: &Callsign) -> Option<&CommissionOverride> {
+        self.overrides
+            .iter()
+            .find(|(cs, _)| cs == seller)
+            .map(|(_, o)| o)
+    }
+
+    /// Return the rate from the bands that applies to the hammer price.
+    #[must_use]
+    pub fn band_rate(&self, hammer_price: &BigDecimal) -> BigDecimal {
+        self.bands
+            .iter()
+            .filter(|b| b.from <= *hammer_price)
+            .max_by(|a, b| a.from.cmp(&b.from))
+            .map_or_else(BigDecimal::zero, |b| b.rate.clone())
+    }
+
+    /// Calculate the commission the club takes from a lot sold by
+    /// `seller` for `hammer_price`.
+    ///
+    /// The rate (from a seller override, or otherwise the bands) is
+    /// applied, then limited by the minimum and maximum fees, then the
+    /// entry fee is added. The commission never exceeds the hammer
+    /// price.
+    #[must_use]
+    pub fn commission_for(&self, seller: &Callsign, hammer_price: &BigDecimal) -> BigDecimal {
+        let rate = match self.override_for(seller) {
+            Some(CommissionOverride::Waived) => return BigDecimal::zero(),
+            Some(CommissionOverride::Rate(rate)) => rate.clone(),
+            None => self.band_rate(hammer_price),
+        };
+
+        let mut commission = hammer_price * rate;
+        if let Some(minimum) = &self.minimum_fee {
+            commission = commission.max(minimum.clone());
+        }
+        if let Some(maximum) = &self.maximum_fee {
+            commission = commission.min(maximum.clone());
+        }
+        commission += &self.entry_fee;
+        commission.min(hammer_price.clone())
+    }
+}
+
+impl fmt::Display for CommissionRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.bands.is_empty() {
+            write!(f, "no commission")?;
+        }
+        for (idx, band) in self.bands.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{band}")?;
+        }
+        if let Some(minimum) = &self.minimum_fee {
+            write!(f, ", minimum fee {minimum}")?;
+        }
+        if let Some(maximum) = &self.maximum_fee {
+            write!(f, ", maximum fee {maximum}")?;
+        }
+        if !self.entry_fee.is_zero() {
+            write!(f, ", entry fee {}", self.entry_fee)?;
+        }
+        for (callsign, over) in &self.overrides {
+            write!(f, ", {over} for {}", callsign.callsign())?;
+        }
+        Ok(())
+    }
+}
+
+/// A commission rate that applies from a hammer price upwards.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters, Setters, WithSetters)]
+#[getset(get = "pub", set = "pub", set_with = "pub")]
+pub struct CommissionBand {
+    /// The hammer price this band applies from
+    from: BigDecimal,
+    /// The rate as a multiplier (i.e. a 10% rate is stored as 0.1)
+    rate: BigDecimal,
+}
+
+impl CommissionBand {
+    /// Create a new band
+    #[must_use]
+    pub fn new(from: BigDecimal, rate: BigDecimal) -> Self {
+        Self { from, rate }
+    }
+}
+
+impl fmt::Display for CommissionBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pct = &self.rate * 100;
+        write!(f, "{pct}% from {}", self.from)
+    }
+}
+
+/// An override of the commission rules for a single seller
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum CommissionOverride {
+    /// The rate as a multiplier, used instead of the bands. Minimum,
+    /// maximum and entry fees still apply.
+    Rate(BigDecimal),
+    /// No commission or fees are taken at all, for example for a silent
+    /// key's estate.
+    Waived,
+}
+
+impl fmt::Display for CommissionOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rate(rate) => {
+                let pct = rate * 100;
+                write!(f, "{pct}%")
+            }
+            Self::Waived => write!(f, "waived"),
+        }
+    }
+}
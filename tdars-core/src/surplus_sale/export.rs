@@ -0,0 +1,590 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    spreadsheet::{self, ExportError},
+    surplus_sale::types::{ReconcileMethod, Reconciliation},
+};
+
+use super::types::Datafile;
+
+use bigdecimal::{BigDecimal, Zero};
+use rust_xlsxwriter::{Format, FormatBorder, Formula, Url, Workbook, Worksheet};
+
+#[allow(
+    clippy::unreadable_literal,
+    reason = "hex colours are better not separated"
+)]
+const ALT_BG: u32 = 0xb4c7dc;
+const COL_LOT: u16 = 1;
+const COL_DESC: u16 = 2;
+const COL_PARTY: u16 = 3;
+const COL_METHOD: u16 = 4;
+const COL_DEBIT: u16 = 5;
+const COL_CREDIT: u16 = 6;
+const COL_BAL: u16 = 7;
+/// The first row of the Settlements sheet below its headings
+const FIRST_SETTLEMENT_ROW: u32 = 4;
+
+/// The rows of the Settlements sheet showing the tenders of a
+/// reconciliation
+#[derive(Clone, Copy)]
+struct SettlementRows<'a> {
+    reconciliation: &'a Reconciliation,
+    first: u32,
+    last: u32,
+}
+
+/// Lay out the reconciliations which haven't been reversed on the
+/// Settlements sheet, one row for each tender
+fn settlement_rows(datafile: &Datafile) -> Result<Vec<SettlementRows<'_>>, ExportError> {
+    let mut rows = vec![];
+    let mut first = FIRST_SETTLEMENT_ROW;
+    for reconciliation in datafile.reconciliations() {
+        if *reconciliation.reversed() {
+            continue;
+        }
+        let len: u32 = reconciliation
+            .tenders()
+            .len()
+            .try_into()
+            .map_err(|_| ExportError::TooManyRows)?;
+        let next = first.checked_add(len).ok_or(ExportError::TooManyRows)?;
+        rows.push(SettlementRows {
+            reconciliation,
+            first,
+            last: next.saturating_sub(1),
+        });
+        first = next;
+    }
+    Ok(rows)
+}
+
+/// Write how a lot was reconciled. A lot settled with more than one
+/// tender may have been paid for from more than one of them, so links to
+/// the rows of its settlement instead.
+fn write_method(
+    worksheet: &mut Worksheet,
+    row: u32,
+    method: &ReconcileMethod,
+    settlement: Option<SettlementRows<'_>>,
+    fmt_reg: &Format,
+    fmt_link: &Format,
+) -> Result<(), ExportError> {
+    match settlement {
+        Some(settlement) if settlement.reconciliation.tenders().len() > 1 => {
+            let methods = settlement
+                .reconciliation
+                .tenders()
+                .iter()
+                .map(|t| t.method().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let link = Url::new(format!(
+                "internal:Settlements!B{}:E{}",
+                settlement.first + 1,
+                settlement.last + 1
+            ))
+            .set_text(format!("Split: {methods}"));
+            worksheet.write_url_with_format(row, COL_METHOD, link, fmt_link)?;
+        }
+        _ => {
+            worksheet.write_with_format(row, COL_METHOD, method.to_string(), fmt_reg)?;
+        }
+    }
+    Ok(())
+}
+
+/// Export the ledger of a sale to an Excel workbook, with sheets for the
+/// transactions, settlements, debtors and audit log.
+///
+/// Amounts are rounded to the minor unit of the sale's currency, see
+/// [`spreadsheet::round`].
+///
+/// # Errors
+///
+/// If an amount can't be written exactly, or the workbook could not be
+/// written.
+pub fn export(datafile: &Datafile) -> Result<Vec<u8>, ExportError> {
+    let mut workbook = Workbook::new();
+
+    // Transactions
+    create_transactions_sheet(&mut workbook, datafile)?;
+
+    // Settlements
+    create_settlements_sheet(&mut workbook, datafile)?;
+
+    // Debtors
+    create_debtors_sheet(&mut workbook, datafile)?;
+
+    // Audit Log
+    create_audit_sheet(&mut workbook, datafile)?;
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+#[allow(
+    clippy::too_many_lines,
+    reason = "this function encapsulates one behaviour"
+)]
+fn create_transactions_sheet(
+    workbook: &mut Workbook,
+    datafile: &Datafile,
+) -> Result<(), ExportError> {
+    let title_format = Format::new().set_bold().set_font_size(28.);
+    let table_heading_format = Format::new()
+        .set_bold()
+        .set_border_bottom(FormatBorder::Medium);
+    let regular_format = Format::new();
+    let alt_format = Format::new().set_background_color(ALT_BG);
+    let open_closing_balance_format = Format::new().set_italic();
+    let open_closing_balance_alt_format: Format = open_closing_balance_format
+        .clone()
+        .set_background_color(ALT_BG);
+
+    let accounting_format =
+        Format::new().set_num_format(spreadsheet::money_format(*datafile.currency()));
+    let accounting_alt_format = accounting_format.clone().set_background_color(ALT_BG);
+    let link_format = Format::new().set_hyperlink();
+    let link_alt_format = link_format.clone().set_background_color(ALT_BG);
+    let minor_units = datafile.currency().exponent();
+    let money = |amount: &BigDecimal| spreadsheet::cell_value(amount, minor_units);
+
+    // The last settlement to reconcile each lot, by its buyer and seller
+    let settlements = settlement_rows(datafile)?;
+    let settled = |lots: fn(&Reconciliation) -> Vec<&String>, lot: &String| {
+        settlements
+            .iter()
+            .rev()
+            .find(|s| lots(s.reconciliation).contains(&lot))
+            .copied()
+    };
+
+    let worksheet = workbook
+        .add_worksheet()
+        .set_name("Transactions")?
+        .set_screen_gridlines(false)
+        .set_print_gridlines(false)
+        .set_freeze_panes(4, 0)?
+        .set_column_width(0, 3)?
+        .set_column_width(COL_LOT, 13)?
+        .set_column_width(COL_DESC, 35)?
+        .set_column_width(COL_PARTY, 25)?
+        .set_column_width(COL_METHOD, 20)?
+        .set_column_width(COL_DEBIT, 10)?
+        .set_column_width(COL_CREDIT, 10)?
+        .set_column_width(COL_BAL, 10)?;
+
+    worksheet.write_with_format(1, 1, "Transactions", &title_format)?;
+
+    worksheet.write_with_format(3, COL_LOT, "Lot", &table_heading_format)?;
+    worksheet.write_with_format(3, COL_DESC, "Description", &table_heading_format)?;
+    worksheet.write_with_format(3, COL_PARTY, "Party", &table_heading_format)?;
+    worksheet.write_with_format(3, COL_METHOD, "Method", &table_heading_format)?;
+    worksheet.write_with_format(3, COL_DEBIT, "Debit", &table_heading_format)?;
+    worksheet.write_with_format(3, COL_CREDIT, "Credit", &table_heading_format)?;
+    worksheet.write_with_format(3, COL_BAL, "Balance", &table_heading_format)?;
+
+    worksheet.write_with_format(4, COL_DESC, "Opening balance", &open_closing_balance_format)?;
+    worksheet.write_with_format(4, COL_BAL, 0, &accounting_format)?;
+
+    let mut row = 5;
+    for item in datafile.items() {
+        if let Some(sold) = &item.sold_details() {
+            if let Some(method) = sold.buyer_reconciled() {
+                let use_alt_format = row % 2 == 1;
+                let fmt_reg = if use_alt_format {
+                    &alt_format
+                } else {
+                    &regular_format
+                };
+                let fmt_acc = if use_alt_format {
+                    &accounting_alt_format
+                } else {
+                    &accounting_format
+                };
+                let fmt_link = if use_alt_format {
+                    &link_alt_format
+                } else {
+                    &link_format
+                };
+
+                worksheet.write_with_format(row, COL_LOT, item.lot_number(), fmt_reg)?;
+                worksheet.write_with_format(row, COL_DESC, item.description(), fmt_reg)?;
+                worksheet.write_with_format(
+                    row,
+                    COL_PARTY,
+                    sold.buyer_callsign().to_string(),
+                    fmt_reg,
+                )?;
+                worksheet.write_with_format(row, COL_DEBIT, "", fmt_reg)?;
+                let settlement = settled(
+                    |r| r.bought_lots().iter().chain(r.collected_lots()).collect(),
+                    item.lot_number(),
+                );
+                write_method(worksheet, row, method, settlement, fmt_reg, fmt_link)?;
+                worksheet.write_with_format(
+                    row,
+                    COL_CREDIT,
+                    money(sold.hammer_price())?,
+                    fmt_acc,
+                )?;
+                worksheet.write_with_format(
+                    row,
+                    COL_BAL,
+                    Formula::new(format!("=H{}-F{}+G{}", row, row + 1, row + 1)),
+                    fmt_acc,
+                )?;
+
+                row += 1;
+            }
+
+            if let Some(method) = sold.seller_reconciled() {
+                let use_alt_format = row % 2 == 1;
+                let fmt_reg = if use_alt_format {
+                    &alt_format
+                } else {
+                    &regular_format
+                };
+                let fmt_acc = if use_alt_format {
+                    &accounting_alt_format
+                } else {
+                    &accounting_format
+                };
+                let fmt_link = if use_alt_format {
+                    &link_alt_format
+                } else {
+                    &link_format
+                };
+
+                worksheet.write_with_format(row, COL_LOT, item.lot_number(), fmt_reg)?;
+                worksheet.write_with_format(row, COL_DESC, item.description(), fmt_reg)?;
+                worksheet.write_with_format(
+                    row,
+                    COL_PARTY,
+                    item.seller_callsign().to_string(),
+                    fmt_reg,
+                )?;
+                let settlement = settled(|r| r.sold_lots().iter().collect(), item.lot_number());
+                write_method(worksheet, row, method, settlement, fmt_reg, fmt_link)?;
+                let hammer_less_club: BigDecimal =
+                    if *sold.seller_reconciled() == Some(ReconcileMethod::Donation) {
+                        BigDecimal::zero()
+                    } else {
+                        sold.seller_proceeds()
+                    };
+                worksheet.write_with_format(row, COL_DEBIT, money(&hammer_less_club)?, fmt_acc)?;
+                worksheet.write_with_format(row, COL_CREDIT, "", fmt_reg)?;
+                worksheet.write_with_format(
+                    row,
+                    COL_BAL,
+                    Formula::new(format!("=H{}-F{}+G{}", row, row + 1, row + 1)),
+                    fmt_acc,
+                )?;
+
+                row += 1;
+            }
+        }
+    }
+
+    for (cs, donation) in datafile.club_donations() {
+        let use_alt_format = row % 2 == 1;
+        let fmt_reg = if use_alt_format {
+            &alt_format
+        } else {
+            &regular_format
+        };
+        let fmt_acc = if use_alt_format {
+            &accounting_alt_format
+        } else {
+            &accounting_format
+        };
+
+        worksheet.write_with_format(row, COL_LOT, "", fmt_reg)?;
+        worksheet.write_with_format(row, COL_DESC, "Club donation", fmt_reg)?;
+        worksheet.write_with_format(row, COL_PARTY, cs.to_string(), fmt_reg)?;
+        worksheet.write_with_format(row, COL_METHOD, "", fmt_reg)?;
+        worksheet.write_with_format(row, COL_DEBIT, "", fmt_reg)?;
+        worksheet.write_with_format(row, COL_CREDIT, money(donation)?, fmt_acc)?;
+        worksheet.write_with_format(
+            row,
+            COL_BAL,
+            Formula::new(format!("=H{}-F{}+G{}", row, row + 1, row + 1)),
+            fmt_acc,
+        )?;
+
+        row += 1;
+    }
+
+    let use_alt_format = row % 2 == 1;
+    let fmt_reg = if use_alt_format {
+        &alt_format
+    } else {
+        &regular_format
+    };
+    let fmt_acc = if use_alt_format {
+        &accounting_alt_format
+    } else {
+        &accounting_format
+    };
+    let fmt_open_close = if use_alt_format {
+        &open_closing_balance_alt_format
+    } else {
+        &open_closing_balance_format
+    };
+    worksheet.write_with_format(row, COL_LOT, "", fmt_reg)?;
+    worksheet.write_with_format(row, COL_DESC, "Closing balance", fmt_open_close)?;
+    worksheet.write_with_format(row, COL_PARTY, "", fmt_reg)?;
+    worksheet.write_with_format(row, COL_METHOD, "", fmt_reg)?;
+    worksheet.write_with_format(row, COL_DEBIT, "", fmt_reg)?;
+    worksheet.write_with_format(row, COL_CREDIT, "", fmt_reg)?;
+    worksheet.write_with_format(row, COL_BAL, Formula::new(format!("=H{row}")), fmt_acc)?;
+
+    Ok(())
+}
+
+#[allow(
+    clippy::too_many_lines,
+    reason = "this function encapsulates one behaviour"
+)]
+fn create_settlements_sheet(
+    workbook: &mut Workbook,
+    datafile: &Datafile,
+) -> Result<(), ExportError> {
+    let title_format = Format::new().set_bold().set_font_size(28.);
+    let table_heading_format = Format::new()
+        .set_bold()
+        .set_border_bottom(FormatBorder::Medium);
+    let regular_format = Format::new();
+    let alt_format = Format::new().set_background_color(ALT_BG);
+
+    let accounting_format =
+        Format::new().set_num_format(spreadsheet::money_format(*datafile.currency()));
+    let accounting_alt_format = accounting_format.clone().set_background_color(ALT_BG);
+    let minor_units = datafile.currency().exponent();
+    let money = |amount: &BigDecimal| spreadsheet::cell_value(amount, minor_units);
+
+    let worksheet = workbook
+        .add_worksheet()
+        .set_name("Settlements")?
+        .set_screen_gridlines(false)
+        .set_print_gridlines(false)
+        .set_freeze_panes(4, 0)?
+        .set_column_width(0, 3)?
+        .set_column_width(1, 25)?
+        .set_column_width(2, 20)?
+        .set_column_width(3, 10)?
+        .set_column_width(4, 10)?;
+
+    worksheet.write_with_format(1, 1, "Settlements", &title_format)?;
+
+    worksheet.write_with_format(3, 1, "Party", &table_heading_format)?;
+    worksheet.write_with_format(3, 2, "Method", &table_heading_format)?;
+    worksheet.write_with_format(3, 3, "Received", &table_heading_format)?;
+    worksheet.write_with_format(3, 4, "Paid", &table_heading_format)?;
+
+    // Received and paid totals, grouped by method
+    let mut totals: BTreeMap<&str, (BigDecimal, BigDecimal)> = BTreeMap::new();
+
+    let settlements = settlement_rows(datafile)?;
+    for settlement in &settlements {
+        let reconciliation = settlement.reconciliation;
+        for (row, tender) in (settlement.first..).zip(reconciliation.tenders()) {
+            let use_alt_format = row % 2 == 1;
+            let fmt_reg = if use_alt_format {
+                &alt_format
+            } else {
+                &regular_format
+            };
+            let fmt_acc = if use_alt_format {
+                &accounting_alt_format
+            } else {
+                &accounting_format
+            };
+            // Totals are of the amounts as written, so that they add up
+            let amount = spreadsheet::round(tender.amount(), minor_units);
+            let value = money(&amount.abs())?;
+            let total = totals.entry(tender.method().group()).or_default();
+            let (received, paid) = if amount < BigDecimal::zero() {
+                total.1 -= amount;
+                (None, Some(value))
+            } else {
+                total.0 += amount;
+                (Some(value), None)
+            };
+
+            worksheet.write_with_format(row, 1, reconciliation.callsign().to_string(), fmt_reg)?;
+            worksheet.write_with_format(row, 2, tender.method().to_string(), fmt_reg)?;
+            if let Some(received) = received {
+                worksheet.write_with_format(row, 3, received, fmt_acc)?;
+            } else {
+                worksheet.write_with_format(row, 3, "", fmt_reg)?;
+            }
+            if let Some(paid) = paid {
+                worksheet.write_with_format(row, 4, paid, fmt_acc)?;
+            } else {
+                worksheet.write_with_format(row, 4, "", fmt_reg)?;
+            }
+        }
+    }
+
+    let mut row = settlements
+        .last()
+        .map_or(FIRST_SETTLEMENT_ROW, |s| s.last + 1);
+    row += 1;
+    worksheet.write_with_format(row, 1, "Totals by Method", &table_heading_format)?;
+    worksheet.write_with_format(row, 2, "", &table_heading_format)?;
+    worksheet.write_with_format(row, 3, "Received", &table_heading_format)?;
+    worksheet.write_with_format(row, 4, "Paid", &table_heading_format)?;
+    row += 1;
+    for (idx, (group, (received, paid))) in totals.iter().enumerate() {
+        let use_alt_format = idx % 2 == 1;
+        let fmt_reg = if use_alt_format {
+            &alt_format
+        } else {
+            &regular_format
+        };
+        let fmt_acc = if use_alt_format {
+            &accounting_alt_format
+        } else {
+            &accounting_format
+        };
+
+        worksheet.write_with_format(row, 1, *group, fmt_reg)?;
+        worksheet.write_with_format(row, 2, "", fmt_reg)?;
+        worksheet.write_with_format(row, 3, money(received)?, fmt_acc)?;
+        worksheet.write_with_format(row, 4, money(paid)?, fmt_acc)?;
+        row += 1;
+    }
+
+    Ok(())
+}
+
+fn create_debtors_sheet(workbook: &mut Workbook, datafile: &Datafile) -> Result<(), ExportError> {
+    let title_format = Format::new().set_bold().set_font_size(28.);
+    let table_heading_format = Format::new()
+        .set_bold()
+        .set_border_bottom(FormatBorder::Medium);
+    let regular_format = Format::new();
+    let alt_format = Format::new().set_background_color(ALT_BG);
+    let date_format = Format::new().set_num_format("YYYY-MM-DD HH:MM");
+    let date_alt_format = date_format.clone().set_background_color(ALT_BG);
+    let accounting_format =
+        Format::new().set_num_format(spreadsheet::money_format(*datafile.currency()));
+    let accounting_alt_format = accounting_format.clone().set_background_color(ALT_BG);
+    let minor_units = datafile.currency().exponent();
+    let money = |amount: &BigDecimal| spreadsheet::cell_value(amount, minor_units);
+
+    let worksheet = workbook
+        .add_worksheet()
+        .set_name("Debtors")?
+        .set_screen_gridlines(false)
+        .set_print_gridlines(false)
+        .set_freeze_panes(4, 0)?
+        .set_column_width(0, 3)?
+        .set_column_width(1, 13)?
+        .set_column_width(2, 25)?
+        .set_column_width(3, 10)?
+        .set_column_width(4, 18)?
+        .set_column_width(5, 60)?;
+
+    worksheet.write_with_format(1, 1, "Debtors", &title_format)?;
+
+    worksheet.write_with_format(3, 1, "Callsign", &table_heading_format)?;
+    worksheet.write_with_format(3, 2, "Name", &table_heading_format)?;
+    worksheet.write_with_format(3, 3, "Owed", &table_heading_format)?;
+    worksheet.write_with_format(3, 4, "Since", &table_heading_format)?;
+    worksheet.write_with_format(3, 5, "Postponed Lots", &table_heading_format)?;
+
+    for (idx, debtor) in datafile.debtors().iter().enumerate() {
+        let use_alt_format = idx % 2 == 1;
+        let fmt_reg = if use_alt_format {
+            &alt_format
+        } else {
+            &regular_format
+        };
+        let fmt_date = if use_alt_format {
+            &date_alt_format
+        } else {
+            &date_format
+        };
+        let fmt_acc = if use_alt_format {
+            &accounting_alt_format
+        } else {
+            &accounting_format
+        };
+
+        let row = (4 + idx).try_into().map_err(|_| ExportError::TooManyRows)?;
+        let lots = debtor
+            .lots()
+            .iter()
+            .map(|i| format!("{} ({})", i.lot_number(), i.description()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        worksheet.write_with_format(row, 1, debtor.callsign().callsign(), fmt_reg)?;
+        worksheet.write_with_format(row, 2, debtor.callsign().name(), fmt_reg)?;
+        worksheet.write_with_format(row, 3, money(debtor.amount())?, fmt_acc)?;
+        if let Some(since) = debtor.since() {
+            worksheet.write_datetime_with_format(row, 4, since.naive_local(), fmt_date)?;
+        } else {
+            worksheet.write_with_format(row, 4, "", fmt_reg)?;
+        }
+        worksheet.write_with_format(row, 5, lots, fmt_reg)?;
+    }
+
+    Ok(())
+}
+
+fn create_audit_sheet(workbook: &mut Workbook, datafile: &Datafile) -> Result<(), ExportError> {
+    let title_format = Format::new().set_bold().set_font_size(28.);
+    let table_heading_format = Format::new()
+        .set_bold()
+        .set_border_bottom(FormatBorder::Medium);
+    let regular_format = Format::new();
+    let alt_format = Format::new().set_background_color(ALT_BG);
+    let audit_date_format = Format::new().set_num_format("YYYY-MM-DD HH:MM:SS.000");
+    let audit_date_alt_format = audit_date_format.clone().set_background_color(ALT_BG);
+
+    let worksheet = workbook
+        .add_worksheet()
+        .set_name("Audit Log")?
+        .set_screen_gridlines(false)
+        .set_print_gridlines(false)
+        .set_freeze_panes(0, 5)?
+        .set_column_width(0, 3)?
+        .set_column_width(1, 25)?
+        .set_column_width(2, 150)?;
+
+    worksheet.write_with_format(1, 1, "Audit Log", &title_format)?;
+    worksheet.write(
+        2,
+        1,
+        format!(
+            "Head hash: {}",
+            datafile.audit_head().as_deref().unwrap_or("none")
+        ),
+    )?;
+
+    worksheet.write_with_format(3, 1, "Timestamp", &table_heading_format)?;
+    worksheet.write_with_format(3, 2, "Event", &table_heading_format)?;
+
+    for (idx, entry) in datafile.audit_log().iter().enumerate() {
+        let use_alt_format = idx % 2 == 1;
+        let fmt_reg = if use_alt_format {
+            &alt_format
+        } else {
+            &regular_format
+        };
+        let fmt_date = if use_alt_format {
+            &audit_date_alt_format
+        } else {
+            &audit_date_format
+        };
+
+        let row = (4 + idx).try_into().map_err(|_| ExportError::TooManyRows)?;
+        worksheet.write_datetime_with_format(row, 1, entry.moment().naive_local(), fmt_date)?;
+        worksheet.write_with_format(row, 2, format!("{}", entry.item()), fmt_reg)?;
+    }
+
+    Ok(())
+}
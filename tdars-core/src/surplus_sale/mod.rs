@@ -0,0 +1,8 @@
+//! Surplus sales, where lots are auctioned on behalf of their sellers
+//! and the club takes a commission.
+
+pub mod commission;
+pub mod export;
+pub mod replay;
+pub mod types;
+pub mod validate;
@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bigdecimal::{BigDecimal, Zero};
+use derive_more::Display;
+use uuid::Uuid;
+
+use crate::{
+    surplus_sale::{
+        commission::CommissionRules,
+        types::{AuditItem, Datafile, Item, ReconcileMethod},
+    },
+    types::Callsign,
+};
+
+/// A difference between the state stored in a datafile and the state
+/// rebuilt by replaying its audit log
+#[derive(Clone, PartialEq, Display)]
+pub enum Discrepancy {
+    #[display("Lot {lot_number} is in the file, but was never recorded in the audit log")]
+    LotNotInLog { lot_number: String },
+    #[display("Lot {lot_number} is recorded in the audit log, but is missing from the file")]
+    LotMissing { lot_number: String },
+    #[display(
+        "Lot {lot_number} has {field} {stored} in the file, but {replayed} in the audit log"
+    )]
+    LotDiffers {
+        lot_number: String,
+        field: &'static str,
+        stored: String,
+        replayed: String,
+    },
+    #[display("{callsign} owes {stored} in the file, but {replayed} in the audit log")]
+    LiabilityDiffers {
+        callsign: Callsign,
+        stored: BigDecimal,
+        replayed: BigDecimal,
+    },
+    #[display("{callsign} has donated {stored} in the file, but {replayed} in the audit log")]
+    DonationsDiffer {
+        callsign: Callsign,
+        stored: BigDecimal,
+        replayed: BigDecimal,
+    },
+    #[display(
+        "The audit log reverses reconciliation {reconciliation}, which could not be replayed"
+    )]
+    ReversalFailed { reconciliation: Uuid },
+    #[display("The audit log amends lot {lot_number}, which could not be replayed")]
+    AmendmentFailed { lot_number: String },
+    #[display(
+        "Lot {lot_number} was amended with commission {logged} in the audit log, but {replayed} when replayed"
+    )]
+    CommissionDiffers {
+        lot_number: String,
+        logged: BigDecimal,
+        replayed: BigDecimal,
+    },
+}
+
+/// Rebuild the state of a datafile purely from its audit log, by
+/// replaying each recorded action on a new datafile. Entries which are
+/// consequences of another action (e.g. change given when reconciling)
+/// are produced again by replaying that action, so are skipped.
+///
+/// Returns the rebuilt datafile, and any reversals or amendments which
+/// could not be replayed as they were logged.
+#[must_use]
+#[allow(
+    clippy::too_many_lines,
+    reason = "this function encapsulates one behaviour"
+)]
+pub fn replay(datafile: &Datafile) -> (Datafile, Vec<Discrepancy>) {
+    let mut replayed = Datafile::new();
+    let mut discrepancies = vec![];
+    // Reconciliations are identified by their audit entry, which differs
+    // once replayed
+    let mut reconciliation_ids: HashMap<Uuid, Uuid> = HashMap::new();
+
+    for entry in datafile.audit_log() {
+        match entry.item() {
+            AuditItem::Created {
+                currency,
+                club_taking_pct,
+            } => {
+                replayed
+                    .set_currency(*currency)
+                    .set_commission_rules(CommissionRules::flat(club_taking_pct / 100));
+            }
+            AuditItem::CurrencyChanged { to, .. } => {
+                replayed.set_currency(*to);
+            }
+            AuditItem::ClubTakingChanged { to_pct, .. } => {
+                replayed.set_commission_rules(CommissionRules::flat(to_pct / 100));
+            }
+            AuditItem::CommissionRulesChanged { to, .. } => {
+                replayed.set_commission_rules(to.clone());
+            }
+            AuditItem::LotSold {
+                lot_number,
+                description,
+                seller,
+                buyer,
+                amount,
+                commission,
+                ..
+            } => {
+                // Older entries didn't record the commission, which is
+                // taken from the rules as they were replayed to this point
+                let commission = commission
+                    .clone()
+                    .unwrap_or_else(|| replayed.commission_for(seller, amount));
+                let mut item = Item::new(lot_number.clone(), seller.clone(), description.clone());
+                item.sold(amount.clone(), buyer.clone(), commission);
+                // Anything rejected when replayed is found as a discrepancy
+                // by comparing the results, so errors are ignored here
+                _ = replayed.push_item(item);
+            }
+            AuditItem::LotNotSold {
+                lot_number,
+                description,
+                seller,
+            } => {
+                // Older files didn't record the seller, but lot numbers
+                // start with the seller's callsign
+                let seller = seller.clone().unwrap_or_else(|| {
+                    let callsign = lot_number
+                        .rsplit_once('-')
+                        .map_or(lot_number.as_str(), |(cs, _)| cs);
+                    Callsign::default().with_callsign(callsign.to_string())
+                });
+                _ = replayed.push_item(Item::new(lot_number.clone(), seller, description.clone()));
+            }
+            AuditItem::Reconciled {
+                callsign,
+                amount,
+                method,
+                ..
+            } => {
+                _ = replayed.reconcile(callsign, amount.clone(), method.clone());
+                if let Some(r) = replayed.last_reconciliation_for(callsign) {
+                    reconciliation_ids.insert(*entry.id(), *r.audit_id());
+                }
+            }
+            AuditItem::Settled {
+                callsign, tenders, ..
+            } => {
+                _ = replayed.settle(callsign, tenders);
+                if let Some(r) = replayed.last_reconciliation_for(callsign) {
+                    reconciliation_ids.insert(*entry.id(), *r.audit_id());
+                }
+            }
+            AuditItem::DebtCollected {
+                callsign, tenders, ..
+            } => {
+                _ = replayed.collect_debt(callsign, tenders);
+                if let Some(r) = replayed.last_reconciliation_for(callsign) {
+                    reconciliation_ids.insert(*entry.id(), *r.audit_id());
+                }
+            }
+            AuditItem::RevokeItem { lot_number } => {
+                _ = replayed.delete_item(lot_number.clone());
+            }
+            AuditItem::LotRestored { item } => {
+                _ = replayed.restore_item((**item).clone());
+            }
+            AuditItem::LotAmended { before, after } => {
+                let sold = after
+                    .sold_details()
+                    .as_ref()
+                    .map(|s| (s.hammer_price().clone(), s.buyer_callsign().clone()));
+                let lot_number = before.lot_number();
+                if replayed
+                    .amend_item(lot_number, after.description().clone(), sold)
+                    .is_err()
+                {
+                    discrepancies.push(Discrepancy::AmendmentFailed {
+                        lot_number: lot_number.clone(),
+                    });
+                } else if let (Some(logged), Some(amended)) = (
+                    after.sold_details(),
+                    replayed
+                        .items()
+                        .iter()
+                        .find(|i| i.lot_number() == lot_number)
+                        .and_then(|i| i.sold_details().as_ref()),
+                ) {
+                    // The commission is recalculated from the rules as
+                    // replayed, which may not be those it was amended under
+                    if logged.commission() != amended.commission() {
+                        discrepancies.push(Discrepancy::CommissionDiffers {
+                            lot_number: lot_number.clone(),
+                            logged: logged.commission().clone(),
+                            replayed: amended.commission().clone(),
+                        });
+                    }
+                }
+            }
+            AuditItem::LotReverted { after, .. } => {
+                if replayed.revert_item((**after).clone()).is_err() {
+                    discrepancies.push(Discrepancy::AmendmentFailed {
+                        lot_number: after.lot_number().clone(),
+                    });
+                }
+            }
+            AuditItem::ReconciliationReversed { reconciliation, .. } => {
+                let reversed = reconciliation_ids
+                    .get(reconciliation)
+                    .is_some_and(|id| replayed.unreconcile(*id).is_ok());
+                if !reversed {
+                    discrepancies.push(Discrepancy::ReversalFailed {
+                        reconciliation: *reconciliation,
+                    });
+                }
+            }
+            AuditItem::LiabilityBroughtForward {
+                callsign,
+                amount,
+                currency,
+                source,
+                source_id,
+            } => {
+                replayed.bring_forward(
+                    callsign.clone(),
+                    amount.clone(),
+                    *currency,
+                    source,
+                    *source_id,
+                );
+            }
+            AuditItem::Repaired { problem } => {
+                replayed.repair(problem);
+            }
+            AuditItem::DonationToClub { .. }
+            | AuditItem::ReconciledFully { .. }
+            | AuditItem::ChangeGiven { .. }
+            | AuditItem::Merged { .. } => (),
+        }
+    }
+
+    (replayed, discrepancies)
+}
+
+/// Replay the audit log of a datafile and compare the rebuilt state with
+/// the lots, liabilities and donations stored in it, returning every
+/// discrepancy found.
+#[must_use]
+pub fn check_integrity(datafile: &Datafile) -> Vec<Discrepancy> {
+    let (replayed, mut discrepancies) = replay(datafile);
+
+    // Lots
+    let stored_items: BTreeMap<_, _> = datafile
+        .items()
+        .iter()
+        .map(|i| (i.lot_number(), i))
+        .collect();
+    let replayed_items: BTreeMap<_, _> = replayed
+        .items()
+        .iter()
+        .map(|i| (i.lot_number(), i))
+        .collect();
+    for (lot_number, stored) in &stored_items {
+        if let Some(replayed) = replayed_items.get(lot_number) {
+            compare_items(stored, replayed, &mut discrepancies);
+        } else {
+            discrepancies.push(Discrepancy::LotNotInLog {
+                lot_number: (*lot_number).clone(),
+            });
+        }
+    }
+    for lot_number in replayed_items.keys() {
+        if !stored_items.contains_key(lot_number) {
+            discrepancies.push(Discrepancy::LotMissing {
+                lot_number: (*lot_number).clone(),
+            });
+        }
+    }
+
+    // Liabilities
+    let mut callsigns: Vec<_> = datafile
+        .callsign_liabilities()
+        .keys()
+        .chain(replayed.callsign_liabilities().keys())
+        .cloned()
+        .collect();
+    callsigns.sort_by(|a, b| a.callsign().cmp(b.callsign()));
+    callsigns.dedup();
+    for callsign in callsigns {
+        let stored = datafile
+            .callsign_liabilities()
+            .get(&callsign)
+            .cloned()
+            .unwrap_or_else(BigDecimal::zero);
+        let replayed = replayed
+            .callsign_liabilities()
+            .get(&callsign)
+            .cloned()
+            .unwrap_or_else(BigDecimal::zero);
+        if stored != replayed {
+            discrepancies.push(Discrepancy::LiabilityDiffers {
+                callsign,
+                stored,
+                replayed,
+            });
+        }
+    }
+
+    // Donations
+    let donations = |datafile: &Datafile| {
+        let mut totals: BTreeMap<String, (Callsign, BigDecimal)> = BTreeMap::new();
+        for (callsign, amount) in datafile.club_donations() {
+            totals
+                .entry(callsign.callsign().clone())
+                .or_insert_with(|| (callsign.clone(), BigDecimal::zero()))
+                .1 += amount;
+        }
+        totals
+    };
+    let stored_donations = donations(datafile);
+    let replayed_donations = donations(&replayed);
+    let mut callsigns: Vec<_> = stored_donations
+        .keys()
+        .chain(replayed_donations.keys())
+        .collect();
+    callsigns.sort();
+    callsigns.dedup();
+    for callsign in callsigns {
+        let (stored_callsign, stored) = stored_donations.get(callsign).cloned().unwrap_or_default();
+        let (replayed_callsign, replayed) = replayed_donations
+            .get(callsign)
+            .cloned()
+            .unwrap_or_default();
+        if stored != replayed {
+            discrepancies.push(Discrepancy::DonationsDiffer {
+                callsign: if stored_callsign.callsign().is_empty() {
+                    replayed_callsign
+                } else {
+                    stored_callsign
+                },
+                stored,
+                replayed,
+            });
+        }
+    }
+
+    discrepancies
+}
+
+/// Compare a stored lot with its replayed counterpart field by field.
+fn compare_items(stored: &Item, replayed: &Item, discrepancies: &mut Vec<Discrepancy>) {
+    let describe = |item: &Item| -> [(&'static str, String); 8] {
+        let sold = item.sold_details().as_ref();
+        let reconciled = |method: Option<&ReconcileMethod>| {
+            method.map_or_else(|| "not reconciled".to_string(), ToString::to_string)
+        };
+        [
+            ("seller", item.seller_callsign().callsign().clone()),
+            ("description", item.description().clone()),
+            ("sold", sold.is_some().to_string()),
+            (
+                "hammer price",
+                sold.map(|s| s.hammer_price().normalized().to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "buyer",
+                sold.map(|s| s.buyer_callsign().callsign().clone())
+                    .unwrap_or_default(),
+            ),
+            (
+                "commission",
+                sold.map(|s| s.commission().normalized().to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "seller reconciliation",
+                reconciled(sold.and_then(|s| s.seller_reconciled().as_ref())),
+            ),
+            (
+                "buyer reconciliation",
+                reconciled(sold.and_then(|s| s.buyer_reconciled().as_ref())),
+            ),
+        ]
+    };
+
+    for ((field, stored_value), (_, replayed_value)) in
+        describe(stored).into_iter().zip(describe(replayed))
+    {
+        if stored_value != replayed_value {
+            discrepancies.push(Discrepancy::LotDiffers {
+                lot_number: stored.lot_number().clone(),
+                field,
+                stored: stored_value,
+                replayed: replayed_value,
+            });
+        }
+    }
+}
@@ -0,0 +1,1976 @@
+#![allow(clippy::ref_option)]
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::{fmt, str::FromStr};
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Local, Utc};
+use derive_more::Display;
+use getset::Getters;
+use iso_currency::Currency;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use uuid::{Builder, Uuid};
+
+use crate::{
+    schema::{self, LoadError, Migration},
+    surplus_sale::{
+        commission::{CommissionOverride, CommissionRules},
+        validate::{self, Problem},
+    },
+    types::Callsign,
+};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct Datafile {
+    /// The schema version of this file
+    version: u64,
+    /// The date and time of the auction
+    auction_date: DateTime<Local>,
+    /// The rules for the commission the club takes. These are applied to
+    /// lots as they are sold.
+    commission_rules: CommissionRules,
+    /// The currency this auction was held under
+    currency: Currency,
+    /// A sorted list of callsigns that have been used in the auction
+    callsigns: Vec<Callsign>,
+    /// A sorted (by lot number) list of items from the auction
+    items: Vec<Item>,
+    /// A map of callsigns that still owe amounts
+    #[serde(with = "liabilities")]
+    callsign_liabilities: HashMap<Callsign, BigDecimal>,
+    /// A list of dontations to the club, callsign and amount
+    club_donations: Vec<(Callsign, BigDecimal)>,
+    /// A list of entries for an audit log
+    audit_log: Vec<AuditEntry>,
+    /// The hash of the last entry in the audit log, which each entry is
+    /// chained back from
+    #[serde(default)]
+    audit_head: Option<String>,
+    /// A record of every reconciliation made, so that they can be reversed
+    #[serde(default)]
+    reconciliations: Vec<Reconciliation>,
+}
+
+impl Default for Datafile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Datafile {
+    /// Create a new datafile, for an auction held now
+    #[must_use]
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the default club taking is validated statically"
+    )]
+    pub fn new() -> Self {
+        let currency = Currency::GBP;
+        #[allow(clippy::unwrap_used, reason = "Default value is validated statically.")]
+        let club_taking = BigDecimal::from_str("0.1").unwrap();
+        let mut datafile = Self {
+            version: schema::current_version(MIGRATIONS),
+            auction_date: Local::now(),
+            commission_rules: CommissionRules::flat(club_taking.clone()),
+            currency,
+            callsigns: vec![],
+            items: vec![],
+            callsign_liabilities: HashMap::new(),
+            club_donations: vec![],
+            audit_log: vec![],
+            audit_head: None,
+            reconciliations: vec![],
+        };
+        datafile.log(AuditEntry::new(AuditItem::Created {
+            currency,
+            club_taking_pct: club_taking * 100,
+        }));
+        datafile
+    }
+
+    /// Load a datafile from its serialised form, migrating it from any
+    /// older version of the file.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be parsed, or was written by a newer version.
+    pub fn load(data: &[u8]) -> Result<Self, LoadError> {
+        let value = schema::migrate(data, MIGRATIONS)?;
+        let datafile: Self = serde_json::from_value(value)?;
+        let unchained = datafile.unchained_entries();
+        if unchained > 0 {
+            tracing::warn!("The first {unchained} audit log entries are not chained");
+        }
+        Ok(datafile)
+    }
+
+    /// Append an entry to the audit log, chaining it to the entry before.
+    /// Entries added to a log written before it was chained are chained
+    /// from the first entry added.
+    fn log(&mut self, mut entry: AuditEntry) {
+        let hash = entry.chain_hash(self.audit_head.as_deref());
+        entry.hash = Some(hash.clone());
+        self.audit_head = Some(hash);
+        self.audit_log.push(entry);
+    }
+
+    /// The number of entries at the start of the audit log which were
+    /// written before the log was chained. These can't be verified, so
+    /// could have been altered without it being detected.
+    #[must_use]
+    pub fn unchained_entries(&self) -> usize {
+        self.audit_log
+            .iter()
+            .take_while(|e| e.hash.is_none())
+            .count()
+    }
+
+    /// Verify that every chained entry in the audit log chains back from
+    /// the head hash, i.e. that no entry has been altered, removed or
+    /// inserted since it was recorded. Entries written before the log was
+    /// chained are skipped, see [`Datafile::unchained_entries`].
+    ///
+    /// # Errors
+    ///
+    /// Describing the first break found in the chain.
+    pub fn verify_audit_chain(&self) -> Result<(), AuditChainError> {
+        let mut previous: Option<String> = None;
+        let unchained = self.unchained_entries();
+        for (idx, entry) in self.audit_log.iter().enumerate().skip(unchained) {
+            let expected = entry.chain_hash(previous.as_deref());
+            if entry.hash.as_ref() != Some(&expected) {
+                return Err(AuditChainError::EntryAltered {
+                    index: idx + 1,
+                    entry: entry.to_string(),
+                });
+            }
+            previous = Some(expected);
+        }
+        if previous != self.audit_head {
+            return Err(AuditChainError::HeadMismatch);
+        }
+        Ok(())
+    }
+
+    /// Return the next lot number for the provided callsign
+    #[must_use]
+    pub fn next_lot_number_for(&self, callsign: &Callsign) -> i32 {
+        let mut next = 1;
+        let cs = callsign.callsign();
+        loop {
+            if !self.items.iter().any(|i| {
+                i.seller_callsign().callsign() == cs && *i.lot_number() == format!("{cs}-{next}")
+            }) {
+                break;
+            }
+            next += 1;
+        }
+        next
+    }
+
+    /// Delete an item if it is not at all reconciled.
+    ///
+    /// # Errors
+    ///
+    /// If the lot does not exist or has been reconciled.
+    pub fn delete_item(&mut self, lot_number: String) -> Result<(), MutationError> {
+        let idx = self.unreconciled_lot(&lot_number)?;
+        self.items.remove(idx);
+        self.log(AuditEntry::new(AuditItem::RevokeItem { lot_number }));
+        Ok(())
+    }
+
+    /// Restore a deleted item exactly as it was.
+    ///
+    /// # Errors
+    ///
+    /// If a lot with the same number already exists.
+    pub fn restore_item(&mut self, item: Item) -> Result<(), MutationError> {
+        self.check_new_lot(&item)?;
+        for callsign in [
+            Some(&item.seller_callsign),
+            item.sold_details.as_ref().map(SoldDetails::buyer_callsign),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !self.callsigns.contains(callsign) {
+                self.callsigns.push(callsign.clone());
+            }
+        }
+        self.log(AuditEntry::new(AuditItem::LotRestored {
+            item: Box::new(item.clone()),
+        }));
+        self.items.push(item);
+        Ok(())
+    }
+
+    /// Amend the description and sale details of a lot. Lots which have
+    /// been reconciled by either party cannot be amended, as the amounts
+    /// already settled would no longer be correct.
+    ///
+    /// # Errors
+    ///
+    /// If the lot does not exist or has been reconciled, or the hammer
+    /// price is negative.
+    pub fn amend_item(
+        &mut self,
+        lot_number: &str,
+        description: String,
+        sold: Option<(BigDecimal, Callsign)>,
+    ) -> Result<(), MutationError> {
+        if sold
+            .as_ref()
+            .is_some_and(|(hammer_price, _)| *hammer_price < BigDecimal::zero())
+        {
+            return Err(MutationError::NegativeAmount);
+        }
+        let idx = self.unreconciled_lot(lot_number)?;
+        let item = &mut self.items[idx];
+
+        let before = item.clone();
+        item.description = description;
+        if let Some((hammer_price, buyer_callsign)) = sold {
+            if !self.callsigns.contains(&buyer_callsign) {
+                self.callsigns.push(buyer_callsign.clone());
+            }
+            // Keep the commission from the original sale unless the hammer
+            // price has changed, in which case it is recalculated
+            let commission = match &before.sold_details {
+                Some(s) if *s.hammer_price() == hammer_price => s.commission.clone(),
+                _ => self
+                    .commission_rules
+                    .commission_for(&before.seller_callsign, &hammer_price),
+            };
+            item.sold(hammer_price, buyer_callsign, commission);
+        } else {
+            item.sold_details = None;
+        }
+        if *item == before {
+            // If there is no change, don't record it
+            return Ok(());
+        }
+
+        let after = item.clone();
+        self.log(AuditEntry::new(AuditItem::LotAmended {
+            before: Box::new(before),
+            after: Box::new(after),
+        }));
+        Ok(())
+    }
+
+    /// Put an amended lot back exactly as it was before, including the
+    /// commission taken, which [`Datafile::amend_item`] would otherwise
+    /// recalculate.
+    ///
+    /// # Errors
+    ///
+    /// If the lot does not exist, or either it or the copy given has been
+    /// reconciled.
+    pub fn revert_item(&mut self, item: Item) -> Result<(), MutationError> {
+        let idx = self.unreconciled_lot(&item.lot_number)?;
+        if item
+            .sold_details
+            .as_ref()
+            .is_some_and(|s| s.buyer_reconciled.is_some() || s.seller_reconciled.is_some())
+        {
+            return Err(MutationError::LotReconciled(item.lot_number));
+        }
+        if self.items[idx] == item {
+            return Ok(());
+        }
+        if let Some(sold) = &item.sold_details {
+            if !self.callsigns.contains(&sold.buyer_callsign) {
+                self.callsigns.push(sold.buyer_callsign.clone());
+            }
+        }
+
+        let before = std::mem::replace(&mut self.items[idx], item.clone());
+        self.log(AuditEntry::new(AuditItem::LotReverted {
+            before: Box::new(before),
+            after: Box::new(item),
+        }));
+        Ok(())
+    }
+
+    /// Find the position of a lot which has not been reconciled by
+    /// either party, and so can still be changed
+    fn unreconciled_lot(&self, lot_number: &str) -> Result<usize, MutationError> {
+        let idx = self
+            .items
+            .iter()
+            .position(|i| i.lot_number == lot_number)
+            .ok_or_else(|| MutationError::LotNotFound(lot_number.to_string()))?;
+        if self.items[idx]
+            .sold_details
+            .as_ref()
+            .is_some_and(|s| s.buyer_reconciled.is_some() || s.seller_reconciled.is_some())
+        {
+            return Err(MutationError::LotReconciled(lot_number.to_string()));
+        }
+        Ok(idx)
+    }
+
+    /// Check that a lot can be added, as its number is not already in
+    /// use and it was not sold for a negative amount
+    fn check_new_lot(&self, item: &Item) -> Result<(), MutationError> {
+        if self.items.iter().any(|i| i.lot_number == item.lot_number) {
+            return Err(MutationError::DuplicateLot(item.lot_number.clone()));
+        }
+        if item
+            .sold_details
+            .as_ref()
+            .is_some_and(|s| s.hammer_price < BigDecimal::zero())
+        {
+            return Err(MutationError::NegativeAmount);
+        }
+        Ok(())
+    }
+
+    /// Set the currency of the auction
+    pub fn set_currency(&mut self, currency: Currency) -> &mut Self {
+        if currency == self.currency {
+            // If there is no change, don't continue
+            return self;
+        }
+
+        let old_currency = self.currency;
+        self.currency = currency;
+        self.log(AuditEntry::new(AuditItem::CurrencyChanged {
+            from: old_currency,
+            to: currency,
+        }));
+        self
+    }
+
+    /// Set the commission rules of the auction
+    pub fn set_commission_rules(&mut self, commission_rules: CommissionRules) -> &mut Self {
+        if commission_rules == self.commission_rules {
+            // If there is no change, don't continue
+            return self;
+        }
+
+        let old_commission_rules = self.commission_rules.clone();
+        self.commission_rules = commission_rules.clone();
+        self.log(AuditEntry::new(AuditItem::CommissionRulesChanged {
+            from: old_commission_rules,
+            to: commission_rules,
+        }));
+        self
+    }
+
+    /// Calculate the commission the club takes on a lot under the
+    /// current rules
+    #[must_use]
+    pub fn commission_for(&self, seller: &Callsign, hammer_price: &BigDecimal) -> BigDecimal {
+        self.commission_rules.commission_for(seller, hammer_price)
+    }
+
+    /// Push an item, sold or unsold
+    ///
+    /// # Errors
+    ///
+    /// If a lot with the same number already exists, or the item was
+    /// sold for a negative amount.
+    pub fn push_item(&mut self, sale: Item) -> Result<&mut Self, MutationError> {
+        self.check_new_lot(&sale)?;
+        let cs = sale.seller_callsign.clone();
+        if !self.callsigns.contains(&cs) {
+            self.callsigns.push(cs);
+        }
+        let cs = sale
+            .sold_details
+            .as_ref()
+            .map(SoldDetails::buyer_callsign)
+            .cloned();
+        if let Some(cs) = cs {
+            if !self.callsigns.contains(&cs) {
+                self.callsigns.push(cs);
+            }
+        }
+
+        if let Some(sold) = sale.sold_details() {
+            self.log(AuditEntry::new(AuditItem::LotSold {
+                lot_number: sale.lot_number().clone(),
+                description: sale.description().clone(),
+                seller: sale.seller_callsign().clone(),
+                buyer: sold.buyer_callsign().clone(),
+                currency: *self.currency(),
+                amount: sold.hammer_price().clone(),
+                commission: Some(sold.commission().clone()),
+            }));
+        } else {
+            self.log(AuditEntry::new(AuditItem::LotNotSold {
+                lot_number: sale.lot_number().clone(),
+                description: sale.description().clone(),
+                seller: Some(sale.seller_callsign().clone()),
+            }));
+        }
+
+        self.items.push(sale);
+        Ok(self)
+    }
+
+    /// Reconcile the callsign by the amount. Returns the amount remaining, i.e. change.
+    ///
+    /// If the club pays out, `reconcile_amount` should be negative. Inverseley if the
+    /// club takes money, `reconcile_amount` should be positive.
+    ///
+    /// # Errors
+    ///
+    /// See [`Datafile::settle`].
+    pub fn reconcile(
+        &mut self,
+        callsign: &Callsign,
+        reconcile_amount: BigDecimal,
+        reconcile_method: ReconcileMethod,
+    ) -> Result<BigDecimal, MutationError> {
+        self.settle(callsign, &[Tender::new(reconcile_method, reconcile_amount)])
+    }
+
+    /// Reconcile the callsign with one or more tenders in a single
+    /// settlement, for example part cash and part bank transfer. Returns
+    /// the amount remaining, i.e. change, which is always given back
+    /// using the last tender.
+    ///
+    /// As with [`Datafile::reconcile`], tender amounts should be negative
+    /// if the club pays out, and positive if the club takes money.
+    ///
+    /// Tenders are allocated in the order given, to sold lots if the club
+    /// pays out, or to bought lots if the club takes money. Each of these
+    /// lots is reconciled with the method of the tender its allocation
+    /// starts in. Lots on the other side only offset the amount and are
+    /// reconciled with the method of the first tender that isn't a
+    /// donation.
+    ///
+    /// Postponed tenders from a callsign paying the club are not money
+    /// received, so are left owing as a liability. Money received pays
+    /// off any existing liability first, and the lots which were
+    /// postponed are reconciled with the method that paid for them.
+    ///
+    /// # Errors
+    ///
+    /// If no tenders are given, or nothing is owed either way and none of
+    /// the tenders is a donation.
+    #[allow(
+        clippy::too_many_lines,
+        reason = "this function encapsulates one behaviour"
+    )]
+    pub fn settle(
+        &mut self,
+        callsign: &Callsign,
+        tenders: &[Tender],
+    ) -> Result<BigDecimal, MutationError> {
+        let (Some(first), Some(last)) = (tenders.first(), tenders.last()) else {
+            return Err(MutationError::NothingTendered);
+        };
+        if !self.anything_owed(callsign)
+            && !tenders
+                .iter()
+                .any(|t| t.method == ReconcileMethod::Donation)
+        {
+            return Err(MutationError::NothingOwed(callsign.callsign().clone()));
+        }
+        let last_method = last.method.clone();
+        let offset_method = tenders
+            .iter()
+            .find(|t| t.method != ReconcileMethod::Donation)
+            .unwrap_or(first)
+            .method
+            .clone();
+        let tendered: BigDecimal = tenders.iter().map(|t| &t.amount).sum();
+        let club_pays = tendered < BigDecimal::zero();
+        let mut reconcile_amount: BigDecimal = tenders
+            .iter()
+            .filter(|t| club_pays || t.method != ReconcileMethod::Postpone)
+            .map(|t| &t.amount)
+            .sum();
+        let mut allocator = TenderAllocator::new(tenders);
+
+        let item = if let [tender] = tenders {
+            AuditItem::Reconciled {
+                callsign: callsign.clone(),
+                amount: tendered.clone(),
+                currency: *self.currency(),
+                method: tender.method.clone(),
+            }
+        } else {
+            AuditItem::Settled {
+                callsign: callsign.clone(),
+                amount: tendered.clone(),
+                currency: *self.currency(),
+                tenders: tenders.to_vec(),
+            }
+        };
+        let entry = AuditEntry::new(item);
+        let mut record = Reconciliation {
+            audit_id: *entry.id(),
+            callsign: callsign.clone(),
+            amount: tendered,
+            tenders: tenders.to_vec(),
+            sold_lots: vec![],
+            bought_lots: vec![],
+            collected_lots: vec![],
+            liability_before: self.callsign_liabilities.get(callsign).cloned(),
+            donation: None,
+            reversed: false,
+        };
+        self.log(entry);
+        let curr = *self.currency();
+        // Sold items first
+        let audit_items = self
+            .items
+            .iter_mut()
+            .filter(|i| i.seller_callsign() == callsign)
+            .filter_map(|i| {
+                // Item sold by CS
+                if let Some(sold) = &mut i.sold_details {
+                    if sold.seller_reconciled.is_some() {
+                        return None;
+                    }
+                    let amt = sold.seller_proceeds();
+                    let method = if club_pays {
+                        allocator.allocate(&amt)
+                    } else {
+                        offset_method.clone()
+                    };
+                    reconcile_amount += amt.clone();
+                    let donated = method == ReconcileMethod::Donation;
+                    sold.seller_reconciled = Some(method);
+                    record.sold_lots.push(i.lot_number.clone());
+                    if donated {
+                        return Some(AuditEntry::new(AuditItem::DonationToClub {
+                            callsign: callsign.clone(),
+                            amount: amt.clone(),
+                            currency: curr,
+                        }));
+                    }
+                }
+                None
+            })
+            .collect::<Vec<_>>();
+
+        for entry in audit_items {
+            self.log(entry);
+        }
+
+        // Liabilities at the highest point
+        if let Some(due) = self.callsign_liabilities.get_mut(callsign) {
+            let dues_paid = due.clone().min(reconcile_amount.clone());
+            *due -= dues_paid.clone();
+            reconcile_amount -= dues_paid.clone();
+            let cleared = due.is_zero();
+            if !club_pays {
+                record.collected_lots = Self::collect_postponed(
+                    &mut self.items,
+                    callsign,
+                    dues_paid,
+                    cleared,
+                    &mut allocator,
+                );
+            }
+        }
+
+        // Then bought items
+        self.items
+            .iter_mut()
+            .filter(|i| {
+                i.sold_details()
+                    .as_ref()
+                    .is_some_and(|s| s.buyer_callsign() == callsign)
+            })
+            .for_each(|i| {
+                // Item bought by CS
+                if let Some(sold) = &mut i.sold_details {
+                    if sold.buyer_reconciled.is_some() {
+                        return;
+                    }
+                    let amt = sold.hammer_price().clone();
+                    let method = if club_pays {
+                        offset_method.clone()
+                    } else {
+                        allocator.allocate(&amt)
+                    };
+                    reconcile_amount -= amt.clone();
+                    sold.buyer_reconciled = Some(method);
+                    record.bought_lots.push(i.lot_number.clone());
+                }
+            });
+
+        if reconcile_amount < BigDecimal::zero() {
+            // Store amount still owe
+            self.callsign_liabilities
+                .entry(callsign.clone())
+                .and_modify(|lia| *lia -= reconcile_amount.clone())
+                .or_insert(-reconcile_amount.clone());
+        } else {
+            self.log(AuditEntry::new(AuditItem::ReconciledFully {
+                callsign: callsign.clone(),
+            }));
+            if reconcile_amount > BigDecimal::zero() && last_method != ReconcileMethod::Donation {
+                // Change returned
+                self.log(AuditEntry::new(AuditItem::ChangeGiven {
+                    callsign: callsign.clone(),
+                    amount: reconcile_amount.clone(),
+                    currency: *self.currency(),
+                }));
+            }
+        }
+
+        if !club_pays {
+            self.pay_postponed(callsign, &record, tenders);
+        }
+
+        let change = reconcile_amount.max(BigDecimal::zero());
+        if change > BigDecimal::zero() && last_method == ReconcileMethod::Donation {
+            // Donate change to club
+            self.log(AuditEntry::new(AuditItem::DonationToClub {
+                callsign: callsign.clone(),
+                amount: change.clone(),
+                currency: *self.currency(),
+            }));
+            self.club_donations.push((callsign.clone(), change.clone()));
+            record.donation = Some(change);
+            self.reconciliations.push(record);
+            Ok(BigDecimal::zero())
+        } else {
+            self.reconciliations.push(record);
+            Ok(change)
+        }
+    }
+
+    /// Collect some or all of what a callsign owes from lots they
+    /// postponed paying for, or brought forward from a previous auction.
+    /// Returns the amount remaining, i.e. change.
+    ///
+    /// Unlike [`Datafile::settle`], only the liability and the postponed
+    /// lots are reconciled, so any lots the callsign has bought or sold
+    /// since are left to be reconciled as usual. Postponed lots which are
+    /// now paid for take the method of the tender that paid for them, and
+    /// postponed tenders are not money received.
+    ///
+    /// # Errors
+    ///
+    /// If no tenders are given, any tender is negative, or the callsign
+    /// owes nothing.
+    pub fn collect_debt(
+        &mut self,
+        callsign: &Callsign,
+        tenders: &[Tender],
+    ) -> Result<BigDecimal, MutationError> {
+        if tenders.is_empty() {
+            return Err(MutationError::NothingTendered);
+        }
+        if tenders.iter().any(|t| t.amount < BigDecimal::zero()) {
+            return Err(MutationError::NegativeAmount);
+        }
+        let Some(due) = self
+            .callsign_liabilities
+            .get(callsign)
+            .filter(|l| **l > BigDecimal::zero())
+            .cloned()
+        else {
+            return Err(MutationError::NothingOwed(callsign.callsign().clone()));
+        };
+        let tendered: BigDecimal = tenders.iter().map(|t| &t.amount).sum();
+        let received: BigDecimal = tenders
+            .iter()
+            .filter(|t| t.method != ReconcileMethod::Postpone)
+            .map(|t| &t.amount)
+            .sum();
+        let paid = due.clone().min(received.clone());
+
+        let entry = AuditEntry::new(AuditItem::DebtCollected {
+            callsign: callsign.clone(),
+            amount: tendered.clone(),
+            currency: self.currency,
+            tenders: tenders.to_vec(),
+        });
+        let mut record = Reconciliation {
+            audit_id: entry.id,
+            callsign: callsign.clone(),
+            amount: tendered,
+            tenders: tenders.to_vec(),
+            sold_lots: vec![],
+            bought_lots: vec![],
+            collected_lots: vec![],
+            liability_before: Some(due.clone()),
+            donation: None,
+            reversed: false,
+        };
+        self.log(entry);
+
+        let remaining = due - &paid;
+        let cleared = remaining.is_zero();
+        self.callsign_liabilities
+            .insert(callsign.clone(), remaining);
+        record.collected_lots = Self::collect_postponed(
+            &mut self.items,
+            callsign,
+            paid.clone(),
+            cleared,
+            &mut TenderAllocator::new(tenders),
+        );
+
+        if cleared {
+            self.pay_postponed(callsign, &record, tenders);
+            self.log(AuditEntry::new(AuditItem::ReconciledFully {
+                callsign: callsign.clone(),
+            }));
+        }
+        let change = received - paid;
+        if change > BigDecimal::zero() {
+            self.log(AuditEntry::new(AuditItem::ChangeGiven {
+                callsign: callsign.clone(),
+                amount: change.clone(),
+                currency: self.currency,
+            }));
+        }
+        self.reconciliations.push(record);
+        Ok(change)
+    }
+
+    /// Mark the callsign's postponed lots as paid for by the amount paid
+    /// off their liability, each taking the method of the tender its
+    /// payment starts in. Returns the numbers of the lots paid for.
+    ///
+    /// A lot is only paid for once the whole of its hammer price has
+    /// been, unless the liability has been cleared, in which case nothing
+    /// is owed for any of them. What is owed can be less than the lots
+    /// postponed, as the proceeds of lots sold offset it.
+    fn collect_postponed(
+        items: &mut [Item],
+        callsign: &Callsign,
+        paid: BigDecimal,
+        cleared: bool,
+        allocator: &mut TenderAllocator,
+    ) -> Vec<String> {
+        let mut remaining = paid;
+        let mut collected = vec![];
+        for i in items {
+            let Some(sold) = &mut i.sold_details else {
+                continue;
+            };
+            if sold.buyer_callsign() != callsign
+                || sold.buyer_reconciled != Some(ReconcileMethod::Postpone)
+                || (!cleared && remaining < sold.hammer_price)
+            {
+                continue;
+            }
+            let amount = sold.hammer_price.clone().min(remaining.clone());
+            remaining -= &amount;
+            sold.buyer_reconciled = Some(allocator.allocate(&amount));
+            collected.push(i.lot_number.clone());
+        }
+        allocator.allocate(&remaining);
+        collected
+    }
+
+    /// Once the callsign owes nothing, mark the lots they paid for in a
+    /// reconciliation which were allocated a postponed tender as paid by
+    /// the first tender that was money received. Nothing is left to be
+    /// paid for them, even if a postponed tender was given.
+    fn pay_postponed(&mut self, callsign: &Callsign, record: &Reconciliation, tenders: &[Tender]) {
+        if self
+            .callsign_liabilities
+            .get(callsign)
+            .is_some_and(|l| *l > BigDecimal::zero())
+        {
+            return;
+        }
+        // With no money received, the lots were paid for by what the
+        // callsign sold
+        let method = tenders
+            .iter()
+            .map(|t| &t.method)
+            .find(|m| !matches!(m, ReconcileMethod::Postpone | ReconcileMethod::Donation))
+            .cloned()
+            .unwrap_or(ReconcileMethod::Cash);
+        for i in &mut self.items {
+            let Some(sold) = &mut i.sold_details else {
+                continue;
+            };
+            if sold.buyer_reconciled == Some(ReconcileMethod::Postpone)
+                && (record.bought_lots.contains(&i.lot_number)
+                    || record.collected_lots.contains(&i.lot_number))
+            {
+                sold.buyer_reconciled = Some(method.clone());
+            }
+        }
+    }
+
+    /// Whether anything is owed either way between the club and the
+    /// callsign, from lots not yet reconciled or an unpaid liability
+    fn anything_owed(&self, callsign: &Callsign) -> bool {
+        self.callsign_liabilities
+            .get(callsign)
+            .is_some_and(|l| *l > BigDecimal::zero())
+            || self.items.iter().any(|i| {
+                i.sold_details.as_ref().is_some_and(|s| {
+                    (i.seller_callsign == *callsign && s.seller_reconciled.is_none())
+                        || (s.buyer_callsign == *callsign && s.buyer_reconciled.is_none())
+                })
+            })
+    }
+
+    /// Return the most recent reconciliation for the callsign that has
+    /// not been reversed. Only this reconciliation can be reversed, as
+    /// any earlier ones may have been built upon.
+    #[must_use]
+    pub fn last_reconciliation_for(&self, callsign: &Callsign) -> Option<&Reconciliation> {
+        self.reconciliations
+            .iter()
+            .rev()
+            .find(|r| r.callsign() == callsign && !r.reversed())
+    }
+
+    /// Reverse a reconciliation, restoring the reconciliation state of
+    /// each lot, the callsign's liability and any donation to exactly
+    /// how they were before.
+    ///
+    /// # Errors
+    ///
+    /// If the reconciliation cannot be found or has already been
+    /// reversed, or is not the most recent for its callsign.
+    pub fn unreconcile(&mut self, audit_id: Uuid) -> Result<(), MutationError> {
+        let idx = self
+            .reconciliations
+            .iter()
+            .position(|r| r.audit_id == audit_id && !r.reversed)
+            .ok_or(MutationError::ReconciliationNotFound)?;
+        let callsign = self.reconciliations[idx].callsign.clone();
+        if self
+            .last_reconciliation_for(&callsign)
+            .is_none_or(|r| r.audit_id != audit_id)
+        {
+            return Err(MutationError::LaterReconciliation(
+                callsign.callsign().clone(),
+            ));
+        }
+
+        let record = &mut self.reconciliations[idx];
+        record.reversed = true;
+        let amount = record.amount.clone();
+        let tenders = record.tenders.clone();
+        for item in &mut self.items {
+            if let Some(sold) = &mut item.sold_details {
+                if record.sold_lots.contains(&item.lot_number) {
+                    sold.seller_reconciled = None;
+                }
+                if record.bought_lots.contains(&item.lot_number) {
+                    sold.buyer_reconciled = None;
+                }
+                if record.collected_lots.contains(&item.lot_number) {
+                    sold.buyer_reconciled = Some(ReconcileMethod::Postpone);
+                }
+            }
+        }
+
+        if let Some(liability) = &record.liability_before {
+            self.callsign_liabilities
+                .insert(callsign.clone(), liability.clone());
+        } else {
+            self.callsign_liabilities.remove(&callsign);
+        }
+
+        if let Some(donation) = &record.donation {
+            if let Some(pos) = self
+                .club_donations
+                .iter()
+                .rposition(|(cs, amt)| *cs == callsign && amt == donation)
+            {
+                self.club_donations.remove(pos);
+            }
+        }
+
+        self.log(AuditEntry::new(AuditItem::ReconciliationReversed {
+            reconciliation: audit_id,
+            callsign,
+            amount,
+            currency: self.currency,
+            tenders,
+        }));
+        Ok(())
+    }
+
+    /// Fix a problem found by [`validate::validate`], recording the
+    /// repair in the audit log. Returns `false` if the problem can't be
+    /// repaired automatically, see [`Problem::is_repairable`].
+    pub fn repair(&mut self, problem: &Problem) -> bool {
+        match problem {
+            Problem::UnknownCallsign { callsign, .. } => {
+                if !self.callsigns.contains(callsign) {
+                    self.callsigns.push(callsign.clone());
+                }
+            }
+            Problem::DuplicateLot { .. } | Problem::PostponedNotOwed { .. } => return false,
+            Problem::NegativeLiability { callsign, .. } => {
+                self.callsign_liabilities.remove(callsign);
+            }
+            Problem::RateOutOfRange { .. } => {
+                for band in self.commission_rules.bands_mut() {
+                    let rate = validate::clamp_rate(band.rate());
+                    band.set_rate(rate);
+                }
+                for (_, o) in self.commission_rules.overrides_mut() {
+                    if let CommissionOverride::Rate(rate) = o {
+                        *rate = validate::clamp_rate(rate);
+                    }
+                }
+            }
+            Problem::DonationNotLogged { callsign, amount } => {
+                // The club keeps the money, so only the missing entry is
+                // logged
+                self.log(AuditEntry::new(AuditItem::DonationToClub {
+                    callsign: callsign.clone(),
+                    amount: amount.clone(),
+                    currency: self.currency,
+                }));
+            }
+        }
+        self.log(AuditEntry::new(AuditItem::Repaired {
+            problem: problem.clone(),
+        }));
+        true
+    }
+
+    /// Bring forward the unpaid balances from a previous auction's
+    /// datafile, so that they can be collected at this auction. `source`
+    /// names the previous file in the audit log. Returns the number of
+    /// debts brought forward.
+    ///
+    /// # Errors
+    ///
+    /// If the previous auction was in a different currency, or its
+    /// balances have already been brought forward.
+    pub fn bring_forward_liabilities(
+        &mut self,
+        source: &str,
+        previous: &Datafile,
+    ) -> Result<usize, MutationError> {
+        if previous.currency != self.currency {
+            return Err(MutationError::CurrencyMismatch {
+                expected: self.currency,
+                found: previous.currency,
+            });
+        }
+        // A previous auction is known by the entry that created it, so
+        // that it is recognised even if the file has been renamed. Older
+        // files are given the same ID for it each time they are loaded.
+        let source_id = previous.audit_log.first().map(|e| e.id);
+        let already = self.audit_log.iter().any(|e| match &e.item {
+            AuditItem::LiabilityBroughtForward {
+                source: s,
+                source_id: id,
+                ..
+            } => match (id, source_id) {
+                (Some(id), Some(source_id)) => *id == source_id,
+                _ => s == source,
+            },
+            _ => false,
+        });
+        if already {
+            return Err(MutationError::AlreadyBroughtForward(source.to_string()));
+        }
+
+        let debtors = previous.debtors();
+        for debtor in &debtors {
+            self.bring_forward(
+                debtor.callsign().clone(),
+                debtor.amount().clone(),
+                previous.currency,
+                source,
+                source_id,
+            );
+        }
+        Ok(debtors.len())
+    }
+
+    /// Bring forward a single debt owed by the callsign.
+    pub(super) fn bring_forward(
+        &mut self,
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+        source: &str,
+        source_id: Option<Uuid>,
+    ) {
+        if !self.callsigns.contains(&callsign) {
+            self.callsigns.push(callsign.clone());
+        }
+        *self
+            .callsign_liabilities
+            .entry(callsign.clone())
+            .or_insert_with(BigDecimal::zero) += &amount;
+        self.log(AuditEntry::new(AuditItem::LiabilityBroughtForward {
+            callsign,
+            amount,
+            currency,
+            source: source.to_string(),
+            source_id,
+        }));
+    }
+
+    /// Return every callsign that still owes the club money, with the
+    /// lots they postponed paying for, largest debt first.
+    #[must_use]
+    pub fn debtors(&self) -> Vec<Debtor> {
+        let mut debtors = self
+            .callsign_liabilities
+            .iter()
+            .filter(|(_, amount)| **amount > BigDecimal::zero())
+            .map(|(callsign, amount)| {
+                let callsign = self
+                    .callsigns
+                    .iter()
+                    .find(|cs| *cs == callsign)
+                    .unwrap_or(callsign)
+                    .clone();
+                let lots = self
+                    .items
+                    .iter()
+                    .filter(|i| {
+                        i.sold_details.as_ref().is_some_and(|s| {
+                            s.buyer_callsign == callsign
+                                && s.buyer_reconciled == Some(ReconcileMethod::Postpone)
+                        })
+                    })
+                    .cloned()
+                    .collect();
+                // The debt started with the last reconciliation made while
+                // the callsign owed nothing
+                let since = self
+                    .reconciliations
+                    .iter()
+                    .rev()
+                    .filter(|r| r.callsign == callsign && !r.reversed)
+                    .find(|r| {
+                        r.liability_before
+                            .as_ref()
+                            .is_none_or(|l| *l <= BigDecimal::zero())
+                    })
+                    .and_then(|r| self.audit_log.iter().find(|e| e.id == r.audit_id))
+                    .map(|e| e.moment);
+                Debtor {
+                    callsign,
+                    amount: amount.clone(),
+                    lots,
+                    since,
+                }
+            })
+            .collect::<Vec<_>>();
+        debtors.sort_by(|a, b| {
+            b.amount
+                .cmp(&a.amount)
+                .then_with(|| a.callsign.callsign().cmp(b.callsign.callsign()))
+        });
+        debtors
+    }
+
+    /// Merge another copy of this auction, for example one recorded on a
+    /// separate laptop, into a new datafile. `source` names the other file
+    /// in the audit log.
+    ///
+    /// Lots are matched by lot number, and where both copies hold a lot
+    /// which differs only by one copy having reconciled it, the
+    /// reconciliation is kept. Lots deleted in either copy since they were
+    /// last added are left out. Audit logs are interleaved by time and
+    /// chained afresh, with the heads of both chains recorded in the
+    /// `Merged` entry. Each callsign's unpaid amount and donations are
+    /// taken from whichever copy has seen every reconciliation the other
+    /// has. Anything else that differs is a conflict, which keeps this
+    /// copy's version and is listed in the returned report.
+    ///
+    /// # Errors
+    ///
+    /// If the audit log of either copy has been tampered with, as it
+    /// can't be trusted to be chained afresh.
+    #[allow(
+        clippy::too_many_lines,
+        reason = "this function encapsulates one behaviour"
+    )]
+    pub fn merge(
+        &self,
+        other: &Datafile,
+        source: &str,
+    ) -> Result<(Datafile, Vec<MergeConflict>), MergeError> {
+        for (file, datafile) in [("this", self), ("the other", other)] {
+            datafile
+                .verify_audit_chain()
+                .map_err(|error| MergeError::AuditChainBroken { file, error })?;
+        }
+
+        let mut conflicts = vec![];
+        let mut merged = self.clone();
+        if self.currency != other.currency {
+            conflicts.push(MergeConflict::CurrencyDiffers {
+                ours: self.currency,
+                theirs: other.currency,
+            });
+        }
+        if self.commission_rules != other.commission_rules {
+            conflicts.push(MergeConflict::CommissionRulesDiffer {
+                ours: Box::new(self.commission_rules.clone()),
+                theirs: Box::new(other.commission_rules.clone()),
+            });
+        }
+        merged.auction_date = self.auction_date.min(other.auction_date);
+        for callsign in &other.callsigns {
+            if !merged.callsigns.contains(callsign) {
+                merged.callsigns.push(callsign.clone());
+            }
+        }
+
+        // Lots
+        for theirs in &other.items {
+            match merged
+                .items
+                .iter_mut()
+                .find(|i| i.lot_number == theirs.lot_number)
+            {
+                None => merged.items.push(theirs.clone()),
+                Some(ours) => {
+                    if let Some(item) = ours.merged_with(theirs) {
+                        *ours = item;
+                    } else {
+                        conflicts.push(MergeConflict::LotDiffers {
+                            ours: Box::new(ours.clone()),
+                            theirs: Box::new(theirs.clone()),
+                        });
+                    }
+                }
+            }
+        }
+
+        // Lots deleted in either copy, unless they have since been
+        // reconciled in the other
+        let revoked = Self::revoked_lots(self.audit_log.iter().chain(&other.audit_log));
+        merged.items.retain(|item| {
+            if !revoked.contains(item.lot_number.as_str()) {
+                return true;
+            }
+            let reconciled = item
+                .sold_details
+                .as_ref()
+                .is_some_and(|s| s.buyer_reconciled.is_some() || s.seller_reconciled.is_some());
+            if reconciled {
+                conflicts.push(MergeConflict::RevokedLotReconciled {
+                    item: Box::new(item.clone()),
+                });
+            }
+            reconciled
+        });
+
+        // Reconciliations
+        for theirs in &other.reconciliations {
+            if let Some(ours) = merged
+                .reconciliations
+                .iter_mut()
+                .find(|r| r.audit_id == theirs.audit_id)
+            {
+                ours.reversed |= theirs.reversed;
+            } else {
+                merged.reconciliations.push(theirs.clone());
+            }
+        }
+
+        // Unpaid amounts and donations
+        let mut callsigns = self
+            .callsign_liabilities
+            .keys()
+            .chain(other.callsign_liabilities.keys())
+            .chain(self.club_donations.iter().map(|(cs, _)| cs))
+            .chain(other.club_donations.iter().map(|(cs, _)| cs))
+            .cloned()
+            .collect::<Vec<_>>();
+        callsigns.sort_by(|a, b| a.callsign().cmp(b.callsign()));
+        callsigns.dedup();
+        for callsign in callsigns {
+            let ours = self.account_of(&callsign);
+            let theirs = other.account_of(&callsign);
+            if ours == theirs {
+                continue;
+            }
+            let our_history = self.account_history(&callsign);
+            let their_history = other.account_history(&callsign);
+            if our_history == their_history
+                || !(our_history.is_superset(&their_history)
+                    || their_history.is_superset(&our_history))
+            {
+                conflicts.push(MergeConflict::AccountDiverged { callsign });
+            } else if their_history.is_superset(&our_history) {
+                if let Some(liability) = theirs.0 {
+                    merged
+                        .callsign_liabilities
+                        .insert(callsign.clone(), liability);
+                } else {
+                    merged.callsign_liabilities.remove(&callsign);
+                }
+                merged.club_donations.retain(|(cs, _)| *cs != callsign);
+                merged
+                    .club_donations
+                    .extend(theirs.1.into_iter().map(|d| (callsign.clone(), d)));
+            }
+        }
+
+        // Audit log, with the entries both copies share kept once. Entries
+        // from older files are matched too, as they are given IDs by their
+        // contents when loaded.
+        let mut entries = self.audit_log.clone();
+        for entry in &other.audit_log {
+            if !entries.iter().any(|e| e.id == entry.id) {
+                entries.push(entry.clone());
+            }
+        }
+        entries.sort_by_key(|e| e.moment);
+        merged.audit_log = vec![];
+        merged.audit_head = None;
+        for mut entry in entries {
+            entry.hash = None;
+            merged.log(entry);
+        }
+        let audit_log = &merged.audit_log;
+        merged
+            .reconciliations
+            .sort_by_key(|r| audit_log.iter().position(|e| e.id == r.audit_id));
+        merged.log(AuditEntry::new(AuditItem::Merged {
+            source: source.to_string(),
+            conflicts: conflicts.len(),
+            our_head: self.audit_head.clone(),
+            their_head: other.audit_head.clone(),
+        }));
+
+        Ok((merged, conflicts))
+    }
+
+    /// The numbers of the lots which were deleted after they were last
+    /// added, going by the audit entries given
+    fn revoked_lots<'a>(entries: impl Iterator<Item = &'a AuditEntry>) -> HashSet<&'a str> {
+        let mut added: HashMap<&str, DateTime<Local>> = HashMap::new();
+        let mut revoked: HashMap<&str, DateTime<Local>> = HashMap::new();
+        for entry in entries {
+            let (lot_number, moments) = match &entry.item {
+                AuditItem::LotSold { lot_number, .. }
+                | AuditItem::LotNotSold { lot_number, .. } => (lot_number, &mut added),
+                AuditItem::LotRestored { item } => (&item.lot_number, &mut added),
+                AuditItem::RevokeItem { lot_number } => (lot_number, &mut revoked),
+                _ => continue,
+            };
+            let moment = moments.entry(lot_number).or_insert(entry.moment);
+            *moment = (*moment).max(entry.moment);
+        }
+        revoked
+            .into_iter()
+            .filter(|(lot_number, moment)| added.get(lot_number).is_none_or(|a| a < moment))
+            .map(|(lot_number, _)| lot_number)
+            .collect()
+    }
+
+    /// The unpaid amount and donations of a callsign
+    fn account_of(&self, callsign: &Callsign) -> (Option<BigDecimal>, Vec<BigDecimal>) {
+        let mut donations = self
+            .club_donations
+            .iter()
+            .filter(|(cs, _)| cs == callsign)
+            .map(|(_, d)| d.clone())
+            .collect::<Vec<_>>();
+        donations.sort();
+        (self.callsign_liabilities.get(callsign).cloned(), donations)
+    }
+
+    /// The IDs of every audit entry which changed the unpaid amount or
+    /// donations of a callsign
+    fn account_history(&self, callsign: &Callsign) -> BTreeSet<Uuid> {
+        self.audit_log
+            .iter()
+            .filter(|e| match &e.item {
+                AuditItem::Reconciled { callsign: cs, .. }
+                | AuditItem::Settled { callsign: cs, .. }
+                | AuditItem::DebtCollected { callsign: cs, .. }
+                | AuditItem::ReconciliationReversed { callsign: cs, .. }
+                | AuditItem::LiabilityBroughtForward { callsign: cs, .. } => cs == callsign,
+                _ => false,
+            })
+            .map(|e| e.id)
+            .collect()
+    }
+}
+
+/// A difference between two copies of an auction which could not be
+/// resolved when merging them
+#[derive(Clone, PartialEq, Display)]
+pub enum MergeConflict {
+    #[display("The currency is {ours} in this file, but {theirs} in the other; kept {ours}")]
+    CurrencyDiffers { ours: Currency, theirs: Currency },
+    #[display(
+        "The commission rules are {ours} in this file, but {theirs} in the other; kept this file's"
+    )]
+    CommissionRulesDiffer {
+        ours: Box<CommissionRules>,
+        theirs: Box<CommissionRules>,
+    },
+    #[display("The {ours} in this file, but the {theirs} in the other; kept this file's")]
+    LotDiffers { ours: Box<Item>, theirs: Box<Item> },
+    #[display("{callsign} has reconciled separately in both files; kept this file's unpaid amount and donations")]
+    AccountDiverged { callsign: Callsign },
+    #[display("The {item} was deleted in one file, but reconciled in the other; kept it")]
+    RevokedLotReconciled { item: Box<Item> },
+}
+
+/// The reasons two copies of an auction can't be merged
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum MergeError {
+    #[display("the audit log of {file} file has been tampered with, so can't be trusted: {error}")]
+    AuditChainBroken {
+        file: &'static str,
+        error: AuditChainError,
+    },
+}
+
+/// Migrations from every previous version of the datafile, see
+/// [`schema::migrate`].
+const MIGRATIONS: &[Migration] = &[
+    migrate_commission_rules,
+    migrate_liabilities_as_pairs,
+    migrate_audit_entry_ids,
+];
+
+/// Version 0 to 1: files written before commission rules were introduced
+/// had a single club taking, which applied to every lot. The commission
+/// for each lot is taken from the club taking at the time of sale, or
+/// failing that the file-level club taking.
+fn migrate_commission_rules(file: &mut Map<String, Value>) -> serde_json::Result<()> {
+    let club_taking = file
+        .get("club_taking")
+        .cloned()
+        .map(serde_json::from_value::<BigDecimal>)
+        .transpose()?;
+    if let Some(club_taking) = &club_taking {
+        if !file.contains_key("commission_rules") {
+            file.insert(
+                "commission_rules".to_string(),
+                serde_json::to_value(CommissionRules::flat(club_taking.clone()))?,
+            );
+        }
+    }
+
+    let sold_lots = file
+        .get_mut("items")
+        .and_then(|i| i.as_array_mut())
+        .into_iter()
+        .flatten()
+        .filter_map(|i| i.get_mut("sold_details"))
+        .filter_map(|s| s.as_object_mut());
+    for sold in sold_lots {
+        if sold.contains_key("commission") {
+            continue;
+        }
+        let lot_taking = sold
+            .remove("club_taking")
+            .map(serde_json::from_value::<BigDecimal>)
+            .transpose()?;
+        let hammer_price = sold
+            .get("hammer_price")
+            .cloned()
+            .map(serde_json::from_value::<BigDecimal>)
+            .transpose()?;
+        if let (Some(taking), Some(hammer_price)) =
+            (lot_taking.or_else(|| club_taking.clone()), hammer_price)
+        {
+            sold.insert(
+                "commission".to_string(),
+                serde_json::to_value(hammer_price * taking)?,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Version 1 to 2: liabilities were stored as a map keyed by callsign
+/// alone, and are now stored as a list of callsign and amount pairs.
+fn migrate_liabilities_as_pairs(file: &mut Map<String, Value>) -> serde_json::Result<()> {
+    if let Some(Value::Object(liabilities)) = file.get("callsign_liabilities") {
+        let pairs = liabilities
+            .iter()
+            .map(|(callsign, amount)| {
+                Ok((
+                    Callsign::default().with_callsign(callsign.clone()),
+                    serde_json::from_value::<BigDecimal>(amount.clone())?,
+                ))
+            })
+            .collect::<serde_json::Result<Vec<_>>>()?;
+        file.insert(
+            "callsign_liabilities".to_string(),
+            serde_json::to_value(pairs)?,
+        );
+    }
+    Ok(())
+}
+
+/// Version 2 to 3: audit entries written before they had IDs are given
+/// one derived from their moment and item, so that every copy of a file
+/// gives an entry the same ID.
+fn migrate_audit_entry_ids(file: &mut Map<String, Value>) -> serde_json::Result<()> {
+    let entries = file
+        .get_mut("audit_log")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut);
+    for entry in entries {
+        if entry.contains_key("id") {
+            continue;
+        }
+        // The moment is hashed in UTC, as in `AuditEntry::chain_hash`
+        let moment = entry
+            .get("moment")
+            .cloned()
+            .map(serde_json::from_value::<DateTime<Local>>)
+            .transpose()?
+            .map(|m| m.to_utc());
+        let hash = Sha256::digest(serde_json::to_vec(&(moment, entry.get("item")))?);
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        let id = Builder::from_custom_bytes(bytes).into_uuid();
+        entry.insert("id".to_string(), serde_json::to_value(id)?);
+    }
+    Ok(())
+}
+
+/// A callsign that still owes the club money after reconciling
+#[derive(Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct Debtor {
+    /// The callsign that owes, with their contact name
+    callsign: Callsign,
+    /// The amount owed
+    amount: BigDecimal,
+    /// The lots they bought and postponed paying for
+    lots: Vec<Item>,
+    /// When the debt was first taken on, if known
+    since: Option<DateTime<Local>>,
+}
+
+/// (De)serialises the liabilities map as a list of pairs, as callsigns
+/// cannot be used as JSON object keys.
+mod liabilities {
+    use std::collections::HashMap;
+
+    use bigdecimal::BigDecimal;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::types::Callsign;
+
+    pub fn serialize<S>(
+        liabilities: &HashMap<Callsign, BigDecimal>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(liabilities.iter())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<Callsign, BigDecimal>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(Callsign, BigDecimal)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+/// A record of a single reconciliation, holding enough of the state
+/// before it was made for it to be reversed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct Reconciliation {
+    /// The ID of the `Reconciled`, `Settled` or `DebtCollected` audit
+    /// entry for this reconciliation
+    audit_id: Uuid,
+    /// The callsign that reconciled
+    callsign: Callsign,
+    /// The total amount reconciled
+    amount: BigDecimal,
+    /// The tenders used to reconcile
+    tenders: Vec<Tender>,
+    /// The lots which were reconciled by their seller
+    sold_lots: Vec<String>,
+    /// The lots which were reconciled by their buyer
+    bought_lots: Vec<String>,
+    /// The postponed lots which were paid for by their buyer
+    #[serde(default)]
+    collected_lots: Vec<String>,
+    /// The liability of the callsign before reconciling, if any
+    liability_before: Option<BigDecimal>,
+    /// The change donated to the club, if any
+    donation: Option<BigDecimal>,
+    /// Has this reconciliation been reversed?
+    reversed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct Item {
+    /// The unique lot number for this item
+    lot_number: String,
+    /// The callsign of the seller
+    seller_callsign: Callsign,
+    /// The description of this item
+    description: String,
+    /// Details about the item's sale, if it was successful
+    sold_details: Option<SoldDetails>,
+}
+
+impl Item {
+    /// Merge two copies of the same lot which differ only by one copy
+    /// having been reconciled by a party the other hasn't. Returns `None`
+    /// if the copies conflict.
+    fn merged_with(&self, other: &Item) -> Option<Item> {
+        if self == other {
+            return Some(self.clone());
+        }
+        if self.seller_callsign != other.seller_callsign || self.description != other.description {
+            return None;
+        }
+        let (Some(ours), Some(theirs)) = (&self.sold_details, &other.sold_details) else {
+            return None;
+        };
+        let both_reconciled_differently =
+            |a: &Option<ReconcileMethod>, b: &Option<ReconcileMethod>| {
+                a.is_some() && b.is_some() && a != b
+            };
+        if ours.hammer_price != theirs.hammer_price
+            || ours.buyer_callsign != theirs.buyer_callsign
+            || ours.commission != theirs.commission
+            || both_reconciled_differently(&ours.seller_reconciled, &theirs.seller_reconciled)
+            || both_reconciled_differently(&ours.buyer_reconciled, &theirs.buyer_reconciled)
+        {
+            return None;
+        }
+        let mut merged = self.clone();
+        if let Some(sold) = &mut merged.sold_details {
+            sold.seller_reconciled = ours
+                .seller_reconciled
+                .clone()
+                .or_else(|| theirs.seller_reconciled.clone());
+            sold.buyer_reconciled = ours
+                .buyer_reconciled
+                .clone()
+                .or_else(|| theirs.buyer_reconciled.clone());
+        }
+        Some(merged)
+    }
+
+    /// Create a new item
+    #[must_use]
+    pub fn new(lot_number: String, seller_callsign: Callsign, description: String) -> Self {
+        Self {
+            lot_number,
+            seller_callsign,
+            description,
+            sold_details: None,
+        }
+    }
+
+    /// Mark the item as sold, with the commission the club takes from the
+    /// sale
+    pub fn sold(
+        &mut self,
+        hammer_price: BigDecimal,
+        buyer_callsign: Callsign,
+        commission: BigDecimal,
+    ) -> &mut Self {
+        self.sold_details = Some(SoldDetails {
+            hammer_price,
+            buyer_callsign,
+            commission,
+            buyer_reconciled: None,
+            seller_reconciled: None,
+        });
+        self
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lot_number = &self.lot_number;
+        let description = &self.description;
+        let seller = &self.seller_callsign;
+        if let Some(sold) = &self.sold_details {
+            let buyer = sold.buyer_callsign();
+            let amount = sold.hammer_price();
+            write!(
+                f,
+                "lot {lot_number} ({description}) sold by {seller} to {buyer} for {amount}"
+            )
+        } else {
+            write!(f, "lot {lot_number} ({description}) by {seller} not sold")
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct SoldDetails {
+    /// What price was this sold for under the hammer?
+    hammer_price: BigDecimal,
+    /// The callsign of the buyer
+    buyer_callsign: Callsign,
+    /// The commission taken by the club, calculated when this was sold
+    commission: BigDecimal,
+    /// Has the buyer reconciled against this item?
+    buyer_reconciled: Option<ReconcileMethod>,
+    /// Has the seller reconciled against this item?
+    seller_reconciled: Option<ReconcileMethod>,
+}
+
+impl SoldDetails {
+    /// The amount of the hammer price due to the seller
+    #[must_use]
+    pub fn seller_proceeds(&self) -> BigDecimal {
+        &self.hammer_price - &self.commission
+    }
+}
+
+/// A single amount tendered by a particular method as part of a
+/// settlement
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters, Display)]
+#[getset(get = "pub")]
+#[display("{method} {amount}")]
+pub struct Tender {
+    /// How was this amount tendered?
+    method: ReconcileMethod,
+    /// The amount tendered
+    amount: BigDecimal,
+}
+
+impl Tender {
+    /// Create a new tender
+    #[must_use]
+    pub fn new(method: ReconcileMethod, amount: BigDecimal) -> Self {
+        Self { method, amount }
+    }
+}
+
+/// Allocates amounts across a list of tenders in order, to determine
+/// which tender each lot was paid with.
+struct TenderAllocator<'a> {
+    tenders: &'a [Tender],
+    idx: usize,
+    used: BigDecimal,
+}
+
+impl<'a> TenderAllocator<'a> {
+    fn new(tenders: &'a [Tender]) -> Self {
+        Self {
+            tenders,
+            idx: 0,
+            used: BigDecimal::zero(),
+        }
+    }
+
+    /// Allocate an amount, returning the method of the tender the
+    /// allocation starts in. Anything beyond the final tender is
+    /// allocated to it.
+    fn allocate(&mut self, amount: &BigDecimal) -> ReconcileMethod {
+        while self.idx + 1 < self.tenders.len() && self.used >= self.tenders[self.idx].amount.abs()
+        {
+            self.used -= self.tenders[self.idx].amount.abs();
+            self.idx += 1;
+        }
+        self.used += amount.abs();
+        self.tenders[self.idx].method.clone()
+    }
+}
+
+/// Format a list of tenders for display
+#[must_use]
+pub fn display_tenders(tenders: &[Tender]) -> String {
+    tenders
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// How was the amount reconciled?
+#[derive(Serialize, Deserialize, Clone, PartialEq, Display)]
+pub enum ReconcileMethod {
+    /// The buyer/seller (was) paid with cash
+    #[display("Cash")]
+    Cash,
+    /// The seller donated the funds to the club (seller only)
+    #[display("Donation")]
+    Donation,
+    /// The buyer/seller (was) paid by bank transfer
+    #[display("Bank Xfr ({})", if *seen { "seen" } else { "unseen" })]
+    BankTransfer {
+        /// Was evidence of the bank transfer seen?
+        seen: bool,
+    },
+    /// The buyer/seller has agreed to pay at a later date
+    #[display("Postponed")]
+    Postpone,
+    /// The buyer/seller (was) paid by card
+    #[display(
+        "Card{}",
+        reference.as_ref().map(|r| format!(" ({r})")).unwrap_or_default()
+    )]
+    Card {
+        /// The reference given by the card terminal, if recorded
+        #[serde(default)]
+        reference: Option<String>,
+    },
+}
+
+impl ReconcileMethod {
+    /// The name of the group this method is totalled under, ignoring any
+    /// details particular to a single transaction.
+    #[must_use]
+    pub fn group(&self) -> &'static str {
+        match self {
+            Self::Cash => "Cash",
+            Self::Donation => "Donation",
+            Self::BankTransfer { .. } => "Bank Transfer",
+            Self::Postpone => "Postponed",
+            Self::Card { .. } => "Card",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct AuditEntry {
+    /// A unique identifier for this entry
+    id: Uuid,
+    /// The moment the audit event happened
+    moment: DateTime<Local>,
+    /// The item that occurred
+    item: AuditItem,
+    /// The hash of this entry chained to the entry before it
+    #[serde(default)]
+    hash: Option<String>,
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let moment = self.moment.format("%F %T%.3f %Z");
+        let item = &self.item;
+        write!(f, "{moment}: {item}")
+    }
+}
+
+impl AuditEntry {
+    #[must_use]
+    pub fn new(item: AuditItem) -> Self {
+        tracing::info!("New audit event: {item}");
+        Self {
+            id: Uuid::new_v4(),
+            moment: Local::now(),
+            item,
+            hash: None,
+        }
+    }
+
+    /// Calculate the hash of this entry chained to the hash of the entry
+    /// before it. The moment is hashed in UTC so that the hash does not
+    /// depend on the timezone the file is opened in.
+    #[must_use]
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "audit items are always serialisable"
+    )]
+    pub fn chain_hash(&self, previous: Option<&str>) -> String {
+        #[derive(Serialize)]
+        struct Hashed<'a> {
+            id: &'a Uuid,
+            moment: DateTime<Utc>,
+            item: &'a AuditItem,
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(previous.unwrap_or_default().as_bytes());
+        #[allow(clippy::unwrap_used, reason = "audit items are always serialisable")]
+        hasher.update(
+            serde_json::to_vec(&Hashed {
+                id: &self.id,
+                moment: self.moment.to_utc(),
+                item: &self.item,
+            })
+            .unwrap(),
+        );
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// The reasons a change to a datafile can be rejected. Nothing is written
+/// to the audit log for a rejected change.
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum MutationError {
+    #[display("lot {_0} does not exist")]
+    LotNotFound(String),
+    #[display("lot {_0} has already been reconciled")]
+    LotReconciled(String),
+    #[display("lot {_0} already exists")]
+    DuplicateLot(String),
+    #[display("nothing is owed to or by {_0}")]
+    NothingOwed(String),
+    #[display("amounts cannot be negative")]
+    NegativeAmount,
+    #[display("no tenders were given")]
+    NothingTendered,
+    #[display("the reconciliation does not exist or has already been reversed")]
+    ReconciliationNotFound,
+    #[display("{_0} has been reconciled again since")]
+    LaterReconciliation(String),
+    #[display("the previous auction was in {found}, not {expected}")]
+    CurrencyMismatch { expected: Currency, found: Currency },
+    #[display("the unpaid balances from {_0} have already been brought forward")]
+    AlreadyBroughtForward(String),
+}
+
+/// The ways in which the audit log chain can be broken
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum AuditChainError {
+    #[display("audit log entry {index} has been altered, removed or inserted: {entry}")]
+    EntryAltered { index: usize, entry: String },
+    #[display("the end of the audit log has been removed or altered")]
+    HeadMismatch,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Display)]
+pub enum AuditItem {
+    #[display(
+        "The auction was created with currency {currency} and club taking {club_taking_pct}%"
+    )]
+    Created {
+        currency: Currency,
+        club_taking_pct: BigDecimal,
+    },
+    #[display("The system currency has changed from {from} to {to}")]
+    CurrencyChanged { from: Currency, to: Currency },
+    #[display("The club taking has changed from {from_pct}% to {to_pct}%")]
+    ClubTakingChanged {
+        from_pct: BigDecimal,
+        to_pct: BigDecimal,
+    },
+    #[display("The commission rules have changed from {from} to {to}")]
+    CommissionRulesChanged {
+        from: CommissionRules,
+        to: CommissionRules,
+    },
+    #[display(
+        "Lot {lot_number} ({description}) sold by {seller} to {buyer} for {amount} {currency}"
+    )]
+    LotSold {
+        lot_number: String,
+        description: String,
+        seller: Callsign,
+        buyer: Callsign,
+        currency: Currency,
+        amount: BigDecimal,
+        /// The commission taken by the club. Not recorded by older
+        /// versions
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        commission: Option<BigDecimal>,
+    },
+    #[display("Lot {lot_number} ({description}) did not sell")]
+    LotNotSold {
+        lot_number: String,
+        description: String,
+        /// Not recorded by older versions
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        seller: Option<Callsign>,
+    },
+    #[display("{callsign} has reconciled {amount} {currency} via {method}")]
+    Reconciled {
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+        method: ReconcileMethod,
+    },
+    #[display(
+        "{callsign} has reconciled {amount} {currency} via {}",
+        display_tenders(tenders)
+    )]
+    Settled {
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+        tenders: Vec<Tender>,
+    },
+    #[display(
+        "{callsign} has paid {amount} {currency} of what they owe via {}",
+        display_tenders(tenders)
+    )]
+    DebtCollected {
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+        tenders: Vec<Tender>,
+    },
+    #[display("{callsign} has donated {amount} {currency} to the club")]
+    DonationToClub {
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+    },
+    #[display("{callsign} has reconciled fully")]
+    ReconciledFully { callsign: Callsign },
+    #[display("{callsign} has been given change: {amount} {currency}")]
+    ChangeGiven {
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+    },
+    #[display("The lot {lot_number} has been revoked.")]
+    RevokeItem { lot_number: String },
+    #[display("The {item} has been restored")]
+    LotRestored { item: Box<Item> },
+    #[display("The {before} has been amended to {after}")]
+    LotAmended { before: Box<Item>, after: Box<Item> },
+    #[display("The {before} has been reverted to {after}")]
+    LotReverted { before: Box<Item>, after: Box<Item> },
+    #[display(
+        "The reconciliation of {amount} {currency} via {} by {callsign} has been reversed (ref. {reconciliation})",
+        display_tenders(tenders)
+    )]
+    ReconciliationReversed {
+        reconciliation: Uuid,
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+        tenders: Vec<Tender>,
+    },
+    #[display("{callsign} owes {amount} {currency} brought forward from {source}")]
+    LiabilityBroughtForward {
+        callsign: Callsign,
+        amount: BigDecimal,
+        currency: Currency,
+        source: String,
+        /// The ID of the entry that created the previous auction, absent
+        /// from entries written before it was recorded
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source_id: Option<Uuid>,
+    },
+    #[display(
+        "Another copy of the auction was merged in from {source}, with {conflicts} conflict(s)"
+    )]
+    Merged {
+        source: String,
+        conflicts: usize,
+        /// The heads of the audit logs of the two copies merged. Not
+        /// recorded by older versions
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        our_head: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        their_head: Option<String>,
+    },
+    #[display("A problem with the file was repaired: {problem}")]
+    Repaired { problem: Problem },
+}
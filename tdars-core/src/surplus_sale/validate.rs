@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::{BigDecimal, One, Zero};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    surplus_sale::{
+        commission::{CommissionBand, CommissionOverride},
+        types::{AuditItem, Datafile, ReconcileMethod, SoldDetails},
+    },
+    types::Callsign,
+};
+
+/// A rule that a datafile should always keep to, but which has been
+/// broken, for example by the file being edited by hand.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Display)]
+pub enum Problem {
+    #[display("Lot {lot_number} refers to {callsign}, who is missing from the list of callsigns")]
+    UnknownCallsign {
+        lot_number: String,
+        callsign: Callsign,
+    },
+    #[display("Lot number {lot_number} is used by {count} lots")]
+    DuplicateLot { lot_number: String, count: usize },
+    #[display("{callsign} owes a negative amount, {amount}")]
+    NegativeLiability {
+        callsign: Callsign,
+        amount: BigDecimal,
+    },
+    #[display("{callsign} has postponed lots totalling {postponed}, but owes nothing")]
+    PostponedNotOwed {
+        callsign: Callsign,
+        postponed: BigDecimal,
+    },
+    #[display("The commission rate of {}% is not between 0% and 100%", rate * 100)]
+    RateOutOfRange { rate: BigDecimal },
+    #[display("{callsign} has donated {amount}, but the donation is not in the audit log")]
+    DonationNotLogged {
+        callsign: Callsign,
+        amount: BigDecimal,
+    },
+}
+
+impl Problem {
+    /// Whether [`Datafile::repair`] can fix this problem without someone
+    /// deciding how it should be fixed.
+    ///
+    /// What is still owed for postponed lots depends on what the callsign
+    /// sold and paid when they were postponed, so can't be worked out
+    /// again.
+    #[must_use]
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self,
+            Self::DuplicateLot { .. } | Self::PostponedNotOwed { .. }
+        )
+    }
+}
+
+/// Check a datafile against the rules it should always keep to,
+/// returning every problem found.
+#[must_use]
+pub fn validate(datafile: &Datafile) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    // Callsigns and lot numbers
+    let mut lot_numbers: BTreeMap<&String, usize> = BTreeMap::new();
+    for item in datafile.items() {
+        *lot_numbers.entry(item.lot_number()).or_default() += 1;
+        let callsigns = [
+            Some(item.seller_callsign()),
+            item.sold_details()
+                .as_ref()
+                .map(SoldDetails::buyer_callsign),
+        ];
+        for callsign in callsigns.into_iter().flatten() {
+            if !datafile.callsigns().contains(callsign) {
+                problems.push(Problem::UnknownCallsign {
+                    lot_number: item.lot_number().clone(),
+                    callsign: callsign.clone(),
+                });
+            }
+        }
+    }
+    for (lot_number, count) in lot_numbers {
+        if count > 1 {
+            problems.push(Problem::DuplicateLot {
+                lot_number: lot_number.clone(),
+                count,
+            });
+        }
+    }
+
+    // Liabilities
+    let mut liabilities: Vec<_> = datafile.callsign_liabilities().iter().collect();
+    liabilities.sort_by(|(a, _), (b, _)| a.callsign().cmp(b.callsign()));
+    for (callsign, amount) in liabilities {
+        if *amount < BigDecimal::zero() {
+            problems.push(Problem::NegativeLiability {
+                callsign: callsign.clone(),
+                amount: amount.clone(),
+            });
+        }
+    }
+    let mut postponed: BTreeMap<String, (Callsign, BigDecimal)> = BTreeMap::new();
+    for sold in datafile
+        .items()
+        .iter()
+        .filter_map(|i| i.sold_details().as_ref())
+    {
+        if *sold.buyer_reconciled() == Some(ReconcileMethod::Postpone) {
+            postponed
+                .entry(sold.buyer_callsign().callsign().clone())
+                .or_insert_with(|| (sold.buyer_callsign().clone(), BigDecimal::zero()))
+                .1 += sold.hammer_price();
+        }
+    }
+    for (callsign, postponed) in postponed.into_values() {
+        let owed = datafile.callsign_liabilities().get(&callsign);
+        if owed.is_none_or(|owed| *owed <= BigDecimal::zero()) {
+            problems.push(Problem::PostponedNotOwed {
+                callsign,
+                postponed,
+            });
+        }
+    }
+
+    // Commission rates
+    let rules = datafile.commission_rules();
+    let rates =
+        rules
+            .bands()
+            .iter()
+            .map(CommissionBand::rate)
+            .chain(rules.overrides().iter().filter_map(|(_, o)| match o {
+                CommissionOverride::Rate(rate) => Some(rate),
+                CommissionOverride::Waived => None,
+            }));
+    for rate in rates {
+        if !is_valid_rate(rate) {
+            problems.push(Problem::RateOutOfRange { rate: rate.clone() });
+        }
+    }
+
+    // Donations, each of which should have been logged when it was made
+    let mut logged: Vec<_> = datafile
+        .audit_log()
+        .iter()
+        .filter_map(|e| match e.item() {
+            AuditItem::DonationToClub {
+                callsign, amount, ..
+            } => Some((callsign, amount)),
+            _ => None,
+        })
+        .collect();
+    for (callsign, amount) in datafile.club_donations() {
+        if let Some(idx) = logged
+            .iter()
+            .position(|(cs, amt)| *cs == callsign && *amt == amount)
+        {
+            logged.remove(idx);
+        } else {
+            problems.push(Problem::DonationNotLogged {
+                callsign: callsign.clone(),
+                amount: amount.clone(),
+            });
+        }
+    }
+
+    problems
+}
+
+/// Whether a commission rate is between 0% and 100%
+fn is_valid_rate(rate: &BigDecimal) -> bool {
+    *rate >= BigDecimal::zero() && *rate <= BigDecimal::one()
+}
+
+/// Bring a commission rate back within 0% and 100%
+pub(super) fn clamp_rate(rate: &BigDecimal) -> BigDecimal {
+    rate.clone().max(BigDecimal::zero()).min(BigDecimal::one())
+}
@@ -0,0 +1,136 @@
+//! Tests for the audit log of surplus sales, and of files written before
+//! its entries had IDs.
+
+use bigdecimal::BigDecimal;
+use serde_json::Value;
+use tdars_core::{
+    surplus_sale::{
+        replay::{check_integrity, Discrepancy},
+        types::{Datafile, Item, MutationError, ReconcileMethod},
+    },
+    types::Callsign,
+};
+
+/// A datafile as it was written before audit entries had IDs or were
+/// chained
+fn without_ids(datafile: &Datafile) -> Vec<u8> {
+    let mut file = serde_json::to_value(datafile).expect("datafile serialises");
+    file["version"] = Value::from(2);
+    file["audit_head"] = Value::Null;
+    for entry in file["audit_log"]
+        .as_array_mut()
+        .expect("audit log is a list")
+    {
+        let entry = entry.as_object_mut().expect("entry is an object");
+        entry.remove("id");
+        entry.remove("hash");
+    }
+    serde_json::to_vec(&file).expect("file serialises")
+}
+
+#[test]
+fn entries_without_ids_are_given_the_same_ids_every_load() {
+    let old = without_ids(&Datafile::new());
+    let first = Datafile::load(&old).expect("old file loads");
+    let second = Datafile::load(&old).expect("old file loads");
+    assert_eq!(first.audit_log()[0].id(), second.audit_log()[0].id());
+
+    // Once saved, the ID is kept
+    let saved = serde_json::to_vec(&first).expect("datafile serialises");
+    let reloaded = Datafile::load(&saved).expect("saved file loads");
+    assert_eq!(first.audit_log()[0].id(), reloaded.audit_log()[0].id());
+}
+
+#[test]
+fn merging_copies_of_a_file_without_ids_keeps_each_entry_once() {
+    let mut original = Datafile::new();
+    let seller = Callsign::default().with_callsign("M0ABC".to_string());
+    let item = Item::new("1".to_string(), seller, "Lot".to_string());
+    original.push_item(item).expect("lot is new");
+    let old = without_ids(&original);
+
+    let ours = Datafile::load(&old).expect("old file loads");
+    let theirs = Datafile::load(&old).expect("old file loads");
+    let (merged, conflicts) = ours.merge(&theirs, "copy").expect("copies merge");
+
+    assert!(conflicts.is_empty());
+    // Each shared entry, then the merge itself
+    assert_eq!(merged.audit_log().len(), original.audit_log().len() + 1);
+    assert!(merged.verify_audit_chain().is_ok());
+}
+
+#[test]
+fn debts_from_a_file_without_ids_are_brought_forward_once() {
+    let mut previous = Datafile::new();
+    let seller = Callsign::default().with_callsign("M0ABC".to_string());
+    let buyer = Callsign::default().with_callsign("2E0XYZ".to_string());
+    let mut item = Item::new("1".to_string(), seller, "Lot".to_string());
+    item.sold(BigDecimal::from(10), buyer.clone(), BigDecimal::from(1));
+    previous.push_item(item).expect("lot is new");
+    previous
+        .reconcile(&buyer, BigDecimal::from(10), ReconcileMethod::Postpone)
+        .expect("buyer owes");
+    let old = without_ids(&previous);
+
+    let mut datafile = Datafile::new();
+    let first = Datafile::load(&old).expect("old file loads");
+    assert_eq!(datafile.bring_forward_liabilities("2024", &first), Ok(1));
+    let second = Datafile::load(&old).expect("old file loads");
+    assert_eq!(
+        datafile.bring_forward_liabilities("2024 again", &second),
+        Err(MutationError::AlreadyBroughtForward(
+            "2024 again".to_string()
+        ))
+    );
+}
+
+#[test]
+fn amendments_replayed_with_a_different_commission_are_discrepancies() {
+    let mut datafile = Datafile::new();
+    let seller = Callsign::default().with_callsign("M0ABC".to_string());
+    let buyer = Callsign::default().with_callsign("2E0XYZ".to_string());
+    let mut item = Item::new("1".to_string(), seller, "Lot".to_string());
+    item.sold(BigDecimal::from(10), buyer.clone(), BigDecimal::from(1));
+    datafile.push_item(item).expect("lot is new");
+    datafile
+        .amend_item("1", "Lot".to_string(), Some((BigDecimal::from(20), buyer)))
+        .expect("lot is unreconciled");
+    assert!(check_integrity(&datafile).is_empty());
+
+    // The amendment as logged took a commission the rules wouldn't give
+    let mut file = serde_json::to_value(&datafile).expect("datafile serialises");
+    let amended = file["audit_log"]
+        .as_array_mut()
+        .expect("audit log is a list")
+        .last_mut()
+        .expect("the amendment is logged");
+    amended["item"]["LotAmended"]["after"]["sold_details"]["commission"] = Value::from("5");
+    let altered = Datafile::load(&serde_json::to_vec(&file).expect("file serialises"))
+        .expect("altered file loads");
+
+    let discrepancies = check_integrity(&altered);
+    assert!(discrepancies.iter().any(|d| matches!(
+        d,
+        Discrepancy::CommissionDiffers { lot_number, .. } if lot_number == "1"
+    )));
+}
+
+#[test]
+fn reverting_an_amendment_restores_the_lot_exactly() {
+    let mut datafile = Datafile::new();
+    let seller = Callsign::default().with_callsign("M0ABC".to_string());
+    let buyer = Callsign::default().with_callsign("2E0XYZ".to_string());
+    let mut item = Item::new("1".to_string(), seller, "Lot".to_string());
+    item.sold(BigDecimal::from(10), buyer.clone(), BigDecimal::from(3));
+    datafile.push_item(item.clone()).expect("lot is new");
+    datafile
+        .amend_item("1", "Lot".to_string(), Some((BigDecimal::from(20), buyer)))
+        .expect("lot is unreconciled");
+
+    // Amending back would take the commission from the rules instead
+    datafile
+        .revert_item(item.clone())
+        .expect("lot is unreconciled");
+    assert!(datafile.items()[0] == item);
+    assert!(check_integrity(&datafile).is_empty());
+}
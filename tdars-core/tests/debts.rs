@@ -0,0 +1,118 @@
+//! Tests for collecting what callsigns owe after postponing payment.
+
+use bigdecimal::{BigDecimal, Zero};
+use tdars_core::{
+    surplus_sale::{
+        types::{Datafile, Item, MutationError, ReconcileMethod, Tender},
+        validate::validate,
+    },
+    types::Callsign,
+};
+
+fn callsign(callsign: &str) -> Callsign {
+    Callsign::default().with_callsign(callsign.to_string())
+}
+
+/// Record a lot sold for the hammer price, with no commission
+fn sell(datafile: &mut Datafile, lot_number: &str, seller: &str, buyer: &str, hammer_price: u32) {
+    let mut item = Item::new(lot_number.to_string(), callsign(seller), "Lot".to_string());
+    item.sold(
+        BigDecimal::from(hammer_price),
+        callsign(buyer),
+        BigDecimal::zero(),
+    );
+    datafile.push_item(item).expect("lot is new");
+}
+
+/// How the buyer of a lot reconciled it
+fn buyer_reconciled(datafile: &Datafile, lot_number: &str) -> Option<ReconcileMethod> {
+    datafile
+        .items()
+        .iter()
+        .find(|i| i.lot_number() == lot_number)
+        .and_then(|i| i.sold_details().clone())
+        .and_then(|s| s.buyer_reconciled().clone())
+}
+
+/// M0ABC buys a lot for 10 and sells one for 9, and postpones the 1
+/// they owe
+fn offset_debt() -> Datafile {
+    let mut datafile = Datafile::new();
+    sell(&mut datafile, "1", "2E0XYZ", "M0ABC", 10);
+    sell(&mut datafile, "2", "M0ABC", "G4DEF", 9);
+    datafile
+        .reconcile(
+            &callsign("M0ABC"),
+            BigDecimal::from(1),
+            ReconcileMethod::Postpone,
+        )
+        .expect("M0ABC owes");
+    assert!(buyer_reconciled(&datafile, "1") == Some(ReconcileMethod::Postpone));
+    datafile
+}
+
+#[test]
+fn postponed_lots_are_paid_once_the_debt_is_settled() {
+    let mut datafile = offset_debt();
+    datafile
+        .reconcile(
+            &callsign("M0ABC"),
+            BigDecimal::from(1),
+            ReconcileMethod::Cash,
+        )
+        .expect("M0ABC owes");
+
+    assert!(buyer_reconciled(&datafile, "1") == Some(ReconcileMethod::Cash));
+    assert!(datafile.debtors().is_empty());
+}
+
+#[test]
+fn paying_a_postponed_debt_in_full_leaves_nothing_to_repair() {
+    let mut datafile = offset_debt();
+    datafile
+        .reconcile(
+            &callsign("M0ABC"),
+            BigDecimal::from(1),
+            ReconcileMethod::Cash,
+        )
+        .expect("M0ABC owes");
+
+    assert!(validate(&datafile).is_empty());
+}
+
+#[test]
+fn collecting_a_debt_leaves_other_lots_alone() {
+    let mut datafile = offset_debt();
+    // Bought after postponing, so not part of the debt
+    sell(&mut datafile, "3", "G4DEF", "M0ABC", 5);
+
+    let change = datafile
+        .collect_debt(
+            &callsign("M0ABC"),
+            &[Tender::new(ReconcileMethod::Cash, BigDecimal::from(2))],
+        )
+        .expect("M0ABC owes");
+
+    assert_eq!(change, BigDecimal::from(1));
+    assert!(buyer_reconciled(&datafile, "1") == Some(ReconcileMethod::Cash));
+    assert!(buyer_reconciled(&datafile, "3").is_none());
+    assert!(datafile.debtors().is_empty());
+    assert_eq!(
+        datafile.collect_debt(
+            &callsign("M0ABC"),
+            &[Tender::new(ReconcileMethod::Cash, BigDecimal::from(1))],
+        ),
+        Err(MutationError::NothingOwed("M0ABC".to_string()))
+    );
+
+    // Reversing the collection leaves the debt as it was
+    let collection = *datafile
+        .last_reconciliation_for(&callsign("M0ABC"))
+        .expect("the debt was collected")
+        .audit_id();
+    datafile
+        .unreconcile(collection)
+        .expect("collection reverses");
+    assert!(buyer_reconciled(&datafile, "1") == Some(ReconcileMethod::Postpone));
+    assert_eq!(datafile.debtors()[0].amount(), &BigDecimal::from(1));
+}
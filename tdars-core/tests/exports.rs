@@ -0,0 +1,434 @@
+//! Golden-file tests for the Excel exports. Each fixture is exported, the
+//! workbook unzipped and its cells read back, and the balance formulas
+//! evaluated to check that they agree with the datafile.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Cursor, Read},
+    iter,
+    str::FromStr,
+};
+
+use bigdecimal::{BigDecimal, Zero};
+use iso_currency::Currency;
+use tdars_core::{
+    hamfest_table::{self, types::ReceiptLine},
+    spreadsheet::ExportError,
+    surplus_sale::{
+        self,
+        types::{Item, ReconcileMethod},
+    },
+    types::Callsign,
+};
+use zip::ZipArchive;
+
+const SALE: &[u8] = include_bytes!("fixtures/sale.tdars_auction");
+const CLUB_TABLE: &[u8] = include_bytes!("fixtures/club_table.tdars_club_table");
+
+/// The first row of transactions, after the headings and the opening
+/// balance
+const FIRST_ROW: u32 = 6;
+
+/// A cell read back from a worksheet
+enum Cell {
+    Number(BigDecimal),
+    Text(String),
+    Formula(String),
+}
+
+/// A worksheet read back from a workbook
+struct Sheet {
+    /// The cells, by reference, e.g. `H6`
+    cells: HashMap<String, Cell>,
+    /// The locations within the workbook that cells link to, by reference
+    links: HashMap<String, String>,
+}
+
+impl Sheet {
+    /// Read the nth worksheet, counting from 1, of an exported workbook
+    fn read(workbook: &[u8], n: usize) -> Self {
+        let mut archive = ZipArchive::new(Cursor::new(workbook)).expect("workbook is a zip");
+        let mut read = |name: &str| {
+            let mut xml = String::new();
+            archive
+                .by_name(name)
+                .expect("part is in the workbook")
+                .read_to_string(&mut xml)
+                .expect("part is readable");
+            xml
+        };
+        let sheet = read(&format!("xl/worksheets/sheet{n}.xml"));
+        let shared = read("xl/sharedStrings.xml");
+
+        let shared = roxmltree::Document::parse(&shared).expect("shared strings are XML");
+        let strings = shared
+            .descendants()
+            .filter(|n| n.has_tag_name("si"))
+            .map(|si| {
+                si.descendants()
+                    .filter(|n| n.has_tag_name("t"))
+                    .filter_map(|t| t.text())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+
+        let sheet = roxmltree::Document::parse(&sheet).expect("worksheet is XML");
+        let cells = sheet
+            .descendants()
+            .filter(|n| n.has_tag_name("c"))
+            .filter_map(|c| {
+                let reference = c.attribute("r")?.to_string();
+                let child = |tag| {
+                    c.children()
+                        .find(|n| n.has_tag_name(tag))
+                        .and_then(|n| n.text())
+                };
+                let cell = if let Some(formula) = child("f") {
+                    Cell::Formula(formula.to_string())
+                } else if c.attribute("t") == Some("s") {
+                    let idx: usize = child("v")?.parse().expect("string index is a number");
+                    Cell::Text(strings[idx].clone())
+                } else {
+                    Cell::Number(BigDecimal::from_str(child("v")?).expect("value is a number"))
+                };
+                Some((reference, cell))
+            })
+            .collect();
+        let links = sheet
+            .descendants()
+            .filter(|n| n.has_tag_name("hyperlink"))
+            .filter_map(|h| {
+                Some((
+                    h.attribute("ref")?.to_string(),
+                    h.attribute("location")?.to_string(),
+                ))
+            })
+            .collect();
+        Self { cells, links }
+    }
+
+    /// The text of a cell, or an empty string if it is blank
+    fn text(&self, reference: &str) -> &str {
+        match self.cells.get(reference) {
+            Some(Cell::Text(text)) => text,
+            None => "",
+            _ => panic!("{reference} is not text"),
+        }
+    }
+
+    /// The value of a cell, evaluating it if it is a formula. Blank cells
+    /// are zero, as in Excel.
+    fn value(&self, reference: &str) -> BigDecimal {
+        match self.cells.get(reference) {
+            Some(Cell::Number(value)) => value.clone(),
+            Some(Cell::Formula(formula)) => self.evaluate(formula),
+            Some(Cell::Text(text)) if text.is_empty() => BigDecimal::zero(),
+            Some(Cell::Text(text)) => panic!("{reference} is text: {text}"),
+            None => BigDecimal::zero(),
+        }
+    }
+
+    /// Evaluate a formula which adds and subtracts cells, which is all
+    /// the exports write
+    fn evaluate(&self, formula: &str) -> BigDecimal {
+        let formula = formula.strip_prefix('=').unwrap_or(formula);
+        let mut total = BigDecimal::zero();
+        let mut reference = String::new();
+        let mut negate = false;
+        for c in formula.chars().chain(iter::once('+')) {
+            if c == '+' || c == '-' {
+                if !reference.is_empty() {
+                    let value = self.value(&reference);
+                    total += if negate { -value } else { value };
+                    reference.clear();
+                }
+                negate = c == '-';
+            } else {
+                assert!(c.is_ascii_alphanumeric(), "unsupported formula: {formula}");
+                reference.push(c);
+            }
+        }
+        total
+    }
+}
+
+/// A row expected on a transactions sheet: its reference (the lot or
+/// receipt number), debit and credit
+struct Row {
+    reference: String,
+    debit: BigDecimal,
+    credit: BigDecimal,
+}
+
+/// Check each row of a transactions sheet, and that the balance column
+/// runs through them to the closing balance. Returns the closing balance.
+fn check_transactions(sheet: &Sheet, rows: &[Row]) -> BigDecimal {
+    assert_eq!(sheet.text("C5"), "Opening balance");
+    assert_eq!(sheet.value("H5"), BigDecimal::zero());
+
+    let mut balance = BigDecimal::zero();
+    let mut n = FIRST_ROW;
+    for row in rows {
+        balance += &row.credit - &row.debit;
+        assert_eq!(sheet.text(&format!("B{n}")), row.reference, "row {n}");
+        assert_eq!(sheet.value(&format!("F{n}")), row.debit, "debit in row {n}");
+        assert_eq!(
+            sheet.value(&format!("G{n}")),
+            row.credit,
+            "credit in row {n}"
+        );
+        assert_eq!(sheet.value(&format!("H{n}")), balance, "balance in row {n}");
+        n += 1;
+    }
+
+    assert_eq!(sheet.text(&format!("C{n}")), "Closing balance");
+    assert_eq!(sheet.value(&format!("H{n}")), balance, "closing balance");
+    assert!(
+        sheet
+            .cells
+            .keys()
+            .filter_map(|r| r
+                .trim_start_matches(char::is_alphabetic)
+                .parse::<u32>()
+                .ok())
+            .all(|row| row <= n),
+        "rows after the closing balance"
+    );
+    balance
+}
+
+fn amount(amount: &str) -> BigDecimal {
+    BigDecimal::from_str(amount).expect("amount is a number")
+}
+
+/// A sale of a single lot, with both sides reconciled in cash
+fn single_lot_sale(hammer_price: &str, proceeds: &str) -> surplus_sale::types::Datafile {
+    let seller = Callsign::default().with_callsign("M0ABC".to_string());
+    let buyer = Callsign::default().with_callsign("2E0XYZ".to_string());
+    let mut datafile = surplus_sale::types::Datafile::new();
+    let hammer_price = amount(hammer_price);
+    let commission = datafile.commission_for(&seller, &hammer_price);
+    let mut item = Item::new("1".to_string(), seller.clone(), "Lot".to_string());
+    item.sold(hammer_price.clone(), buyer.clone(), commission);
+    datafile.push_item(item).expect("lot is new");
+    datafile
+        .reconcile(&buyer, hammer_price, ReconcileMethod::Cash)
+        .expect("buyer owes");
+    datafile
+        .reconcile(&seller, -amount(proceeds), ReconcileMethod::Cash)
+        .expect("seller is owed");
+    datafile
+}
+
+#[test]
+fn sale_amounts_are_rounded_to_the_penny() {
+    // The seller is owed 7.245 after 10% commission
+    let datafile = single_lot_sale("8.05", "7.245");
+    let workbook = surplus_sale::export::export(&datafile).expect("sale exports");
+
+    // Half a penny is rounded away from zero, and written exactly
+    let sheet = Sheet::read(&workbook, 1);
+    assert_eq!(sheet.value("G6"), amount("8.05"));
+    assert_eq!(sheet.value("F7"), amount("7.25"));
+    assert_eq!(sheet.value("H8"), amount("0.80"));
+
+    let sheet = Sheet::read(&workbook, 2);
+    assert_eq!(sheet.value("E6"), amount("7.25"));
+    assert_eq!(sheet.text("B9"), "Cash");
+    assert_eq!(sheet.value("E9"), amount("7.25"));
+}
+
+#[test]
+fn sale_amounts_are_formatted_in_its_currency() {
+    let mut datafile = single_lot_sale("800", "720");
+    datafile.set_currency(Currency::JPY);
+    let workbook = surplus_sale::export::export(&datafile).expect("sale exports");
+
+    let mut styles = String::new();
+    ZipArchive::new(Cursor::new(workbook))
+        .expect("workbook is a zip")
+        .by_name("xl/styles.xml")
+        .expect("styles are in the workbook")
+        .read_to_string(&mut styles)
+        .expect("styles are readable");
+    // Yen have no minor unit, so no decimal places
+    let symbol = Currency::JPY.symbol();
+    let format = format!(r#"formatCode="[${symbol}]#,##0;[RED]-[${symbol}]#,##0""#);
+    assert!(styles.contains(&format));
+    assert!(!styles.contains('£'));
+}
+
+#[test]
+fn sale_amounts_too_long_to_write_exactly_are_rejected() {
+    let datafile = single_lot_sale("12345678901234567.89", "11111111011111111.101");
+    assert!(matches!(
+        surplus_sale::export::export(&datafile),
+        Err(ExportError::Unrepresentable(_))
+    ));
+}
+
+#[test]
+fn sale_transactions_balance() {
+    let datafile = surplus_sale::types::Datafile::load(SALE).expect("fixture loads");
+    let workbook = surplus_sale::export::export(&datafile).expect("fixture exports");
+    let sheet = Sheet::read(&workbook, 1);
+
+    // Money taken from buyers, and paid to sellers unless they donated
+    // their proceeds, lot by lot, then the change donated
+    let mut rows = vec![];
+    for item in datafile.items() {
+        let Some(sold) = item.sold_details() else {
+            continue;
+        };
+        if sold.buyer_reconciled().is_some() {
+            rows.push(Row {
+                reference: item.lot_number().clone(),
+                debit: BigDecimal::zero(),
+                credit: sold.hammer_price().clone(),
+            });
+        }
+        if let Some(method) = sold.seller_reconciled() {
+            rows.push(Row {
+                reference: item.lot_number().clone(),
+                debit: if *method == ReconcileMethod::Donation {
+                    BigDecimal::zero()
+                } else {
+                    sold.seller_proceeds()
+                },
+                credit: BigDecimal::zero(),
+            });
+        }
+    }
+    for (_, donation) in datafile.club_donations() {
+        rows.push(Row {
+            reference: String::new(),
+            debit: BigDecimal::zero(),
+            credit: donation.clone(),
+        });
+    }
+
+    assert_eq!(rows.len(), 10);
+    assert_eq!(check_transactions(&sheet, &rows), amount("43.55"));
+}
+
+#[test]
+fn sale_settlement_totals_match_tenders() {
+    let datafile = surplus_sale::types::Datafile::load(SALE).expect("fixture loads");
+    let workbook = surplus_sale::export::export(&datafile).expect("fixture exports");
+    let sheet = Sheet::read(&workbook, 2);
+
+    let mut totals: BTreeMap<&str, (BigDecimal, BigDecimal)> = BTreeMap::new();
+    let mut n = 5;
+    for reconciliation in datafile.reconciliations() {
+        if *reconciliation.reversed() {
+            continue;
+        }
+        for tender in reconciliation.tenders() {
+            let total = totals.entry(tender.method().group()).or_default();
+            if *tender.amount() < BigDecimal::zero() {
+                total.1 -= tender.amount();
+            } else {
+                total.0 += tender.amount();
+            }
+            assert_eq!(
+                sheet.text(&format!("B{n}")),
+                reconciliation.callsign().to_string()
+            );
+            assert_eq!(
+                &sheet.value(&format!("D{n}")) - sheet.value(&format!("E{n}")),
+                *tender.amount(),
+                "tender in row {n}"
+            );
+            n += 1;
+        }
+    }
+
+    assert_eq!(sheet.text(&format!("B{}", n + 1)), "Totals by Method");
+    n += 2;
+    for (group, (received, paid)) in &totals {
+        assert_eq!(sheet.text(&format!("B{n}")), *group);
+        assert_eq!(sheet.value(&format!("D{n}")), *received, "{group} received");
+        assert_eq!(sheet.value(&format!("E{n}")), *paid, "{group} paid");
+        n += 1;
+    }
+    assert_eq!(totals.len(), 4);
+}
+
+#[test]
+fn club_table_transactions_balance() {
+    let datafile = hamfest_table::types::Datafile::load(CLUB_TABLE).expect("fixture loads");
+    let workbook = hamfest_table::export::export(&datafile).expect("fixture exports");
+    let sheet = Sheet::read(&workbook, 1);
+
+    let mut rows = vec![];
+    for receipt in datafile.receipts() {
+        for line in receipt.lines() {
+            let (debit, credit) = match line {
+                ReceiptLine::Item { .. } => continue,
+                ReceiptLine::Payment { amount, .. } => (BigDecimal::zero(), amount.clone()),
+                ReceiptLine::Change { amount, .. } => (amount.clone(), BigDecimal::zero()),
+            };
+            rows.push(Row {
+                reference: receipt.number().to_string(),
+                debit,
+                credit,
+            });
+        }
+    }
+
+    // Every receipt was paid in full, so the takings are the prices of
+    // the items sold
+    let sold: BigDecimal = datafile
+        .receipts()
+        .iter()
+        .flat_map(|r| r.lines())
+        .filter_map(|l| match l {
+            ReceiptLine::Item { item } => Some(item.price()),
+            _ => None,
+        })
+        .sum();
+    assert_eq!(rows.len(), 5);
+    let closing = check_transactions(&sheet, &rows);
+    assert_eq!(closing, sold);
+    assert_eq!(closing, amount("56.98"));
+}
+
+#[test]
+fn sale_split_settlements_link_to_their_tenders() {
+    let datafile = surplus_sale::types::Datafile::load(SALE).expect("fixture loads");
+    let workbook = surplus_sale::export::export(&datafile).expect("fixture exports");
+    let transactions = Sheet::read(&workbook, 1);
+    let settlements = Sheet::read(&workbook, 2);
+
+    let mut split = 0;
+    for (reference, location) in &transactions.links {
+        let n = reference.trim_start_matches('E');
+        let (first, last) = location
+            .strip_prefix("Settlements!B")
+            .and_then(|rows| rows.split_once(":E"))
+            .expect("link is to rows of the Settlements sheet");
+        let rows = first.parse::<u32>().expect("row is a number")
+            ..=last.parse::<u32>().expect("row is a number");
+        assert!(rows.clone().count() > 1, "{reference} links to one tender");
+
+        // The settlement is of the party in the same row, and its tenders
+        // are those listed
+        for row in rows.clone() {
+            assert_eq!(
+                settlements.text(&format!("B{row}")),
+                transactions.text(&format!("D{n}")),
+                "party of {reference}"
+            );
+        }
+        let methods = rows
+            .map(|row| settlements.text(&format!("C{row}")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        assert_eq!(transactions.text(reference), format!("Split: {methods}"));
+        split += 1;
+    }
+
+    // Alice sold two lots and bought one, and Carol bought two, both
+    // settling with two tenders
+    assert_eq!(split, 5);
+}
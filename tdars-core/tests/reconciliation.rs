@@ -0,0 +1,416 @@
+//! Property tests for reconciliation. Random auctions are generated and
+//! reconciled in random ways, and the books must balance after every
+//! step.
+
+use bigdecimal::{BigDecimal, Zero};
+use proptest::{prelude::*, sample::Index};
+use tdars_core::{
+    surplus_sale::{
+        commission::CommissionRules,
+        types::{AuditItem, Datafile, Item, MutationError, ReconcileMethod, Tender},
+    },
+    types::Callsign,
+};
+
+const CALLSIGNS: [&str; 4] = ["M0AAA", "2E0BBB", "G4CCC", "Alice"];
+
+/// The methods a callsign can pay the club with
+const PAYING_METHODS: [ReconcileMethod; 4] = [
+    ReconcileMethod::Cash,
+    ReconcileMethod::Card { reference: None },
+    ReconcileMethod::BankTransfer { seen: true },
+    ReconcileMethod::Postpone,
+];
+
+/// The methods the club can pay a callsign with
+const PAID_METHODS: [ReconcileMethod; 3] = [
+    ReconcileMethod::Cash,
+    ReconcileMethod::BankTransfer { seen: false },
+    ReconcileMethod::Donation,
+];
+
+#[derive(Debug, Clone)]
+struct Auction {
+    /// The flat commission rate, in percent
+    rate: u32,
+    /// The minimum commission, in pence
+    minimum_fee: Option<u32>,
+    lots: Vec<Lot>,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+struct Lot {
+    seller: usize,
+    /// The hammer price in pence, and the buyer
+    sold: Option<(u32, usize)>,
+}
+
+/// A reconciliation of one callsign, with each tender a percentage of
+/// what they owe or are owed at the time
+#[derive(Debug, Clone)]
+struct Step {
+    callsign: usize,
+    tenders: Vec<(Index, u32)>,
+    donate_change: bool,
+}
+
+fn auction() -> impl Strategy<Value = Auction> {
+    let lot = (
+        0..CALLSIGNS.len(),
+        proptest::option::weighted(0.8, (0..=5000u32, 0..CALLSIGNS.len())),
+    )
+        .prop_map(|(seller, sold)| Lot { seller, sold });
+    let step = (
+        0..CALLSIGNS.len(),
+        proptest::collection::vec((any::<Index>(), 0..=120u32), 1..=3),
+        any::<bool>(),
+    )
+        .prop_map(|(callsign, tenders, donate_change)| Step {
+            callsign,
+            tenders,
+            donate_change,
+        });
+    (
+        0..=30u32,
+        proptest::option::of(0..=100u32),
+        proptest::collection::vec(lot, 0..12),
+        proptest::collection::vec(step, 0..20),
+    )
+        .prop_map(|(rate, minimum_fee, lots, steps)| Auction {
+            rate,
+            minimum_fee,
+            lots,
+            steps,
+        })
+}
+
+fn callsign(idx: usize) -> Callsign {
+    Callsign::default().with_callsign(CALLSIGNS[idx].to_string())
+}
+
+fn pence(amount: u32) -> BigDecimal {
+    BigDecimal::new(amount.into(), 2)
+}
+
+/// Record the lots of the auction, without reconciling anything
+fn hold(auction: &Auction) -> Datafile {
+    let mut datafile = Datafile::new();
+    datafile.set_commission_rules(
+        CommissionRules::flat(pence(auction.rate)).with_minimum_fee(auction.minimum_fee.map(pence)),
+    );
+    for (idx, lot) in auction.lots.iter().enumerate() {
+        let seller = callsign(lot.seller);
+        let mut item = Item::new((idx + 1).to_string(), seller.clone(), "Lot".to_string());
+        if let Some((hammer_price, buyer)) = lot.sold {
+            let hammer_price = pence(hammer_price);
+            let commission = datafile.commission_for(&seller, &hammer_price);
+            item.sold(hammer_price, callsign(buyer), commission);
+        }
+        datafile.push_item(item).expect("lot numbers are unique");
+    }
+    datafile
+}
+
+/// What the callsign owes the club, or if negative what the club owes
+/// them, as the reconciliation screen shows it
+fn owed(datafile: &Datafile, callsign: &Callsign) -> BigDecimal {
+    let mut total = datafile
+        .callsign_liabilities()
+        .get(callsign)
+        .cloned()
+        .unwrap_or_default();
+    for item in datafile.items() {
+        let Some(sold) = item.sold_details() else {
+            continue;
+        };
+        if sold.buyer_callsign() == callsign && sold.buyer_reconciled().is_none() {
+            total += sold.hammer_price();
+        }
+        if item.seller_callsign() == callsign && sold.seller_reconciled().is_none() {
+            total -= sold.seller_proceeds();
+        }
+    }
+    total
+}
+
+/// Take a step, checking that a rejected reconciliation changes nothing
+fn take(datafile: &mut Datafile, step: &Step) -> Result<(), TestCaseError> {
+    let callsign = callsign(step.callsign);
+    let owed = owed(datafile, &callsign);
+    let methods: &[ReconcileMethod] = if owed < BigDecimal::zero() {
+        &PAID_METHODS
+    } else {
+        &PAYING_METHODS
+    };
+    let mut tenders = step
+        .tenders
+        .iter()
+        .map(|(method, pct)| Tender::new(method.get(methods).clone(), &owed * pence(*pct)))
+        .collect::<Vec<_>>();
+    if step.donate_change {
+        tenders.push(Tender::new(ReconcileMethod::Donation, BigDecimal::zero()));
+    }
+
+    let before = datafile.clone();
+    match datafile.settle(&callsign, &tenders) {
+        Ok(change) => prop_assert!(change >= BigDecimal::zero()),
+        Err(e) => {
+            prop_assert_eq!(e, MutationError::NothingOwed(callsign.callsign().clone()));
+            prop_assert!(
+                *datafile == before,
+                "a rejected reconciliation changed the file"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Settle each callsign in full, in cash
+fn settle_everyone(datafile: &mut Datafile) -> Result<(), TestCaseError> {
+    for idx in 0..CALLSIGNS.len() {
+        let callsign = callsign(idx);
+        let owed = owed(datafile, &callsign);
+        match datafile.reconcile(&callsign, owed, ReconcileMethod::Cash) {
+            Ok(change) => prop_assert_eq!(change, BigDecimal::zero()),
+            Err(e) => prop_assert_eq!(e, MutationError::NothingOwed(callsign.callsign().clone())),
+        }
+    }
+    Ok(())
+}
+
+/// The money that has changed hands with a callsign, and what it was
+/// for
+#[derive(Default)]
+struct Books {
+    /// Money received from the callsign
+    money_in: BigDecimal,
+    /// Money paid to the callsign, including change
+    money_out: BigDecimal,
+    /// Proceeds the callsign gave to the club, and change they didn't
+    /// take
+    donations: BigDecimal,
+    /// The hammer price of the lots the callsign has reconciled as a
+    /// buyer, less the proceeds of those they have reconciled as a
+    /// seller. Once every lot is reconciled, the total of this across
+    /// all callsigns is the commission the club takes.
+    takings: BigDecimal,
+    /// What the callsign still owes
+    owing: BigDecimal,
+}
+
+impl Books {
+    fn of(datafile: &Datafile, callsign: &Callsign) -> Self {
+        let mut books = Self::default();
+        for r in datafile.reconciliations() {
+            if r.callsign() != callsign {
+                continue;
+            }
+            // A promise to pay later is not money received
+            let club_pays = *r.amount() < BigDecimal::zero();
+            for tender in r.tenders() {
+                match tender.method() {
+                    ReconcileMethod::Postpone if !club_pays => {}
+                    ReconcileMethod::Donation => books.donations -= tender.amount(),
+                    _ if *tender.amount() < BigDecimal::zero() => {
+                        books.money_out -= tender.amount();
+                    }
+                    _ => books.money_in += tender.amount(),
+                }
+            }
+            if let Some(donation) = r.donation() {
+                books.donations += donation;
+            }
+        }
+        for entry in datafile.audit_log() {
+            if let AuditItem::ChangeGiven {
+                callsign: cs,
+                amount,
+                ..
+            } = entry.item()
+            {
+                if cs == callsign {
+                    books.money_out += amount;
+                }
+            }
+        }
+        for item in datafile.items() {
+            let Some(sold) = item.sold_details() else {
+                continue;
+            };
+            if sold.buyer_callsign() == callsign && sold.buyer_reconciled().is_some() {
+                books.takings += sold.hammer_price();
+            }
+            if item.seller_callsign() == callsign && sold.seller_reconciled().is_some() {
+                books.takings -= sold.seller_proceeds();
+            }
+        }
+        books.owing = datafile
+            .callsign_liabilities()
+            .get(callsign)
+            .cloned()
+            .unwrap_or_default();
+        books
+    }
+}
+
+/// Check the invariants which hold after every reconciliation
+fn check(datafile: &Datafile) -> Result<(), TestCaseError> {
+    // Money in equals money out plus club takings plus donations, less
+    // whatever is still owed
+    for idx in 0..CALLSIGNS.len() {
+        let callsign = callsign(idx);
+        let books = Books::of(datafile, &callsign);
+        prop_assert_eq!(
+            &books.money_in + &books.owing,
+            &books.money_out + &books.takings + &books.donations,
+            "the books don't balance for {}",
+            callsign.callsign()
+        );
+    }
+
+    for (callsign, liability) in datafile.callsign_liabilities() {
+        prop_assert!(
+            *liability >= BigDecimal::zero(),
+            "{} has a negative liability of {}",
+            callsign.callsign(),
+            liability
+        );
+    }
+
+    // Each side of a lot is reconciled once, by one reconciliation
+    for item in datafile.items() {
+        let Some(sold) = item.sold_details() else {
+            continue;
+        };
+        let lot_number = item.lot_number();
+        let by_seller = datafile
+            .reconciliations()
+            .iter()
+            .filter(|r| r.sold_lots().contains(lot_number))
+            .count();
+        prop_assert_eq!(by_seller, usize::from(sold.seller_reconciled().is_some()));
+        let by_buyer = datafile
+            .reconciliations()
+            .iter()
+            .filter(|r| r.bought_lots().contains(lot_number))
+            .count();
+        prop_assert_eq!(by_buyer, usize::from(sold.buyer_reconciled().is_some()));
+    }
+
+    // Postponed lots are paid for once their buyer owes nothing
+    for item in datafile.items() {
+        let Some(sold) = item.sold_details() else {
+            continue;
+        };
+        let owing = datafile
+            .callsign_liabilities()
+            .get(sold.buyer_callsign())
+            .is_some_and(|l| !l.is_zero());
+        prop_assert!(
+            owing || *sold.buyer_reconciled() != Some(ReconcileMethod::Postpone),
+            "lot {} is still postponed, but {} owes nothing",
+            item.lot_number(),
+            sold.buyer_callsign().callsign()
+        );
+    }
+
+    // Each reconciliation has exactly one audit entry
+    for r in datafile.reconciliations() {
+        let entries = datafile
+            .audit_log()
+            .iter()
+            .filter(|e| e.id() == r.audit_id())
+            .filter(|e| match e.item() {
+                AuditItem::Reconciled { callsign, .. }
+                | AuditItem::Settled { callsign, .. }
+                | AuditItem::DebtCollected { callsign, .. } => callsign == r.callsign(),
+                _ => false,
+            })
+            .count();
+        prop_assert_eq!(entries, 1);
+    }
+    let entries = datafile
+        .audit_log()
+        .iter()
+        .filter(|e| {
+            matches!(
+                e.item(),
+                AuditItem::Reconciled { .. }
+                    | AuditItem::Settled { .. }
+                    | AuditItem::DebtCollected { .. }
+            )
+        })
+        .count();
+    prop_assert_eq!(entries, datafile.reconciliations().len());
+
+    prop_assert!(datafile.verify_audit_chain().is_ok());
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn books_balance_after_every_reconciliation(auction in auction()) {
+        let mut datafile = hold(&auction);
+        check(&datafile)?;
+        for step in &auction.steps {
+            take(&mut datafile, step)?;
+            check(&datafile)?;
+        }
+    }
+
+    #[test]
+    fn settling_in_full_leaves_the_club_its_commission(auction in auction()) {
+        let mut datafile = hold(&auction);
+        for step in &auction.steps {
+            take(&mut datafile, step)?;
+        }
+        settle_everyone(&mut datafile)?;
+        check(&datafile)?;
+
+        for item in datafile.items() {
+            if let Some(sold) = item.sold_details() {
+                prop_assert!(sold.seller_reconciled().is_some());
+                prop_assert!(sold.buyer_reconciled().is_some());
+            }
+        }
+        prop_assert!(datafile.callsign_liabilities().values().all(Zero::is_zero));
+
+        let mut total = Books::default();
+        for idx in 0..CALLSIGNS.len() {
+            let books = Books::of(&datafile, &callsign(idx));
+            total.money_in += books.money_in;
+            total.money_out += books.money_out;
+            total.donations += books.donations;
+            total.takings += books.takings;
+        }
+        let commission: BigDecimal = datafile
+            .items()
+            .iter()
+            .filter_map(|i| i.sold_details().as_ref())
+            .map(|s| s.commission())
+            .sum();
+        prop_assert_eq!(&total.takings, &commission);
+        prop_assert_eq!(total.money_in, total.money_out + commission + total.donations);
+    }
+
+    #[test]
+    fn reconciling_twice_is_a_no_op(auction in auction(), idx in 0..CALLSIGNS.len()) {
+        let mut datafile = hold(&auction);
+        for step in &auction.steps {
+            take(&mut datafile, step)?;
+        }
+        let callsign = callsign(idx);
+        let owed = owed(&datafile, &callsign);
+        let before = datafile.clone();
+        let first = datafile.reconcile(&callsign, owed.clone(), ReconcileMethod::Cash);
+        if first.is_err() {
+            prop_assert!(datafile == before, "a rejected reconciliation changed the file");
+        }
+        let after_first = datafile.clone();
+        let second = datafile.reconcile(&callsign, owed, ReconcileMethod::Cash);
+
+        prop_assert_eq!(second, Err(MutationError::NothingOwed(callsign.callsign().clone())));
+        prop_assert!(datafile == after_first, "reconciling again changed the file");
+    }
+}